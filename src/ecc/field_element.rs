@@ -22,7 +22,7 @@ impl FieldElement {
     }
 
     pub fn pow(self, exponent: BigInt) -> Self {
-        let mut e = (BigInt::from(exponent) + &self.prime - 1) % (&self.prime - BigInt::from(1u8));
+        let mut e = (exponent + &self.prime - 1) % (&self.prime - BigInt::from(1u8));
         let mut current = FieldElement::new(BigInt::one(), self.prime.clone());
         let mut coef = self.clone();
         while e > BigInt::zero() {
@@ -79,7 +79,7 @@ impl_ops::impl_op_ex!(/ |a: &FieldElement, b: &FieldElement| -> FieldElement {
 
     let prime = &a.prime;
     // prime must be more than 2 because this means primary number
-    let b_inverse = b.clone().pow((prime - BigInt::from(2u8)).try_into().unwrap());
+    let b_inverse = b.clone().pow(prime - BigInt::from(2u8));
     let num = (&a.num * b_inverse.num) % prime;
     return FieldElement::new(num, a.prime.clone());
 });
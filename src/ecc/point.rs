@@ -107,6 +107,12 @@ impl N {
     }
 }
 
+impl Default for N {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
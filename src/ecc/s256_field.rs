@@ -1,6 +1,6 @@
 use impl_ops::*;
 use num_bigint::BigInt;
-use num_traits::{One, Zero};
+use num_traits::Zero;
 use std::ops;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -19,17 +19,10 @@ impl S256Field {
     }
 
     pub fn pow(self, exponent: BigInt) -> Self {
-        let mut e = (BigInt::from(exponent) + &self.prime - 1) % (&self.prime - BigInt::from(1u8));
-        let mut current = S256Field::new(BigInt::one());
-        let mut coef = self.clone();
-        while e > BigInt::zero() {
-            if &e % BigInt::from(2u8) == One::one() {
-                current = &current * &coef;
-            }
-            coef = &coef * &coef;
-            e /= 2;
-        }
-        return current;
+        let e = (exponent + &self.prime - 1) % (&self.prime - BigInt::from(1u8));
+        // every point addition inverts through here, and modpow is several times faster than
+        // square-and-multiply on S256Field values
+        return S256Field::new(self.num.modpow(&e, &self.prime));
     }
 
     pub fn rmul(self, r: BigInt) -> Self {
@@ -38,7 +31,7 @@ impl S256Field {
 
     pub fn sqrt(self) -> Self {
         let p = self.prime.clone();
-        return self.rmul((p + 1) / 4);
+        return self.pow((p + 1) / 4);
     }
 }
 
@@ -81,7 +74,7 @@ impl_ops::impl_op_ex!(/ |a: &S256Field, b: &S256Field| -> S256Field {
 
     let prime = &a.prime;
     // prime must be more than 2 because this means primary number
-    let b_inverse = b.clone().pow((prime - BigInt::from(2u8)).try_into().unwrap());
+    let b_inverse = b.clone().pow(prime - BigInt::from(2u8));
     let num = (&a.num * b_inverse.num) % prime;
     return S256Field::new(num);
 });
@@ -1,13 +1,27 @@
+use crate::security::schnorr_signature::SchnorrSignature;
 use crate::security::signature::Signature;
 
 use super::field_element::FieldElement;
 use super::s256_field::S256Field;
-use crate::util::{base58, hash160};
+use crate::network::Network;
+use crate::security::public_key::{PublicKey, PublicKeyError};
+use crate::util::bigint::to_32_bytes;
+use crate::util::hash160;
+use crate::util::hex::{hex, FromHex, ToHex};
+use crate::util::tagged_hash::Tag;
 use impl_ops::*;
 use num_bigint::{BigInt, Sign};
 use num_traits::{One, Zero};
 use std::ops::{self};
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TweakError {
+    // the tweak is not less than the curve order n (or zero for a multiplicative tweak)
+    InvalidTweak,
+    // the tweaked point is the point at infinity, or the tweaked private key is zero
+    Infinity,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct S256Point {
     pub x: Option<S256Field>,
@@ -78,6 +92,28 @@ impl S256Point {
         return result;
     }
 
+    // Montgomery ladder: one addition and one doubling for every one of the 256 bits, so the
+    // sequence of point operations doesn't depend on the coefficient. The BigInt arithmetic
+    // underneath is not constant time.
+    pub fn rmul_ct(self, coefficient: BigInt) -> Self {
+        let n = self.n.clone();
+        let coef = (coefficient % &n + &n) % &n;
+        let mut r0 = Self::new(None, None);
+        let mut r1 = self;
+
+        for i in (0..256).rev() {
+            if coef.bit(i) {
+                r0 = &r0 + &r1;
+                r1 = &r1 + &r1;
+            } else {
+                r1 = &r0 + &r1;
+                r0 = &r0 + &r0;
+            }
+        }
+
+        return r0;
+    }
+
     pub fn verify(self, z: BigInt, sig: Signature) -> bool {
         let n = self.n.clone();
         let s = FieldElement::new(sig.s, n.clone());
@@ -95,7 +131,7 @@ impl S256Point {
             let (_, vec_x) = self.x.unwrap().num.to_bytes_be();
             // 32 byte (vec.len = 32) になるまで 左に zero-padding
             let diff = 32 - vec_x.len();
-            let mut vec_x_with_padding = std::iter::repeat(0x0).take(diff).collect::<Vec<u8>>();
+            let mut vec_x_with_padding = vec![0u8; diff];
             vec_x_with_padding.extend(vec_x);
 
             let marker = if self.y.unwrap().num % BigInt::from(2u8) == BigInt::from(0u8) {
@@ -113,13 +149,13 @@ impl S256Point {
             let (_, vec_x) = self.x.unwrap().num.to_bytes_be();
             let diff_x = 32 - vec_x.len();
             // 32 byte (vec.len = 32) になるまで 左に zero-padding
-            let mut vec_x_with_padding = std::iter::repeat(0x0).take(diff_x).collect::<Vec<u8>>();
+            let mut vec_x_with_padding = vec![0u8; diff_x];
             vec_x_with_padding.extend(vec_x);
 
             let (_, vec_y) = self.y.unwrap().num.to_bytes_be();
             let diff_y = 32 - vec_y.len();
             // 32 byte (vec.len = 32) になるまで 左に zero-padding
-            let mut vec_y_with_padding = std::iter::repeat(0x0).take(diff_y).collect::<Vec<u8>>();
+            let mut vec_y_with_padding = vec![0u8; diff_y];
             vec_y_with_padding.extend(vec_y);
 
             let mut result = Vec::new();
@@ -155,15 +191,87 @@ impl S256Point {
         }
     }
 
+    // BIP340 lift_x: the point with x coordinate x and even y, if it exists
+    pub fn lift_x(x: BigInt) -> Option<Self> {
+        let x_field = S256Field::new(x.clone());
+        // x must be in [0, p)
+        if x.sign() == Sign::Minus || x_field.num != x {
+            return None;
+        }
+        let x = x_field;
+        let alpha = &x * &x * &x + S256Field::new(BigInt::from(7u8));
+        let beta = alpha.clone().sqrt();
+        if &beta * &beta != alpha {
+            return None;
+        }
+        if &beta.num % BigInt::from(2u8) == BigInt::zero() {
+            Some(Self::new(Some(x), Some(beta)))
+        } else {
+            Some(Self::new(Some(x), Some(S256Field::new(&beta.prime - &beta.num))))
+        }
+    }
+
+    // 32 byte x coordinate used as the BIP340 public key
+    pub fn xonly(&self) -> Vec<u8> {
+        to_32_bytes(&self.x.as_ref().unwrap().num)
+    }
+
+    pub fn has_even_y(&self) -> bool {
+        &self.y.as_ref().unwrap().num % BigInt::from(2u8) == BigInt::zero()
+    }
+
+    pub fn verify_schnorr(self, msg: &[u8], sig: SchnorrSignature) -> bool {
+        let n = self.n.clone();
+        let p = match Self::lift_x(self.x.unwrap().num) {
+            Some(p) => p,
+            None => return false,
+        };
+        if sig.r >= p.x.as_ref().unwrap().prime || sig.s >= n {
+            return false;
+        }
+        let mut bytes = to_32_bytes(&sig.r);
+        bytes.extend(p.xonly());
+        bytes.extend(msg);
+        let e = BigInt::from_bytes_be(Sign::Plus, &Tag::Bip340Challenge.hash(&bytes)) % &n;
+        // R = s * G - e * P
+        let total = Self::new_g().rmul(sig.s) + p.rmul(&n - e);
+        match &total.x {
+            Some(x) => total.has_even_y() && x.num == sig.r,
+            None => false,
+        }
+    }
+
+    // self + tweak * G
+    pub fn add_exp_tweak(&self, tweak: &BigInt) -> Result<Self, TweakError> {
+        if tweak.sign() == Sign::Minus || tweak >= &self.n {
+            return Err(TweakError::InvalidTweak);
+        }
+        let result = Self::new_g().rmul(tweak.clone()) + self;
+        match result.x {
+            Some(_) => Ok(result),
+            None => Err(TweakError::Infinity),
+        }
+    }
+
+    // tweak * self
+    pub fn mul_tweak(&self, tweak: &BigInt) -> Result<Self, TweakError> {
+        if tweak.sign() != Sign::Plus || tweak >= &self.n {
+            return Err(TweakError::InvalidTweak);
+        }
+        let result = self.clone().rmul(tweak.clone());
+        match result.x {
+            Some(_) => Ok(result),
+            None => Err(TweakError::Infinity),
+        }
+    }
+
     pub fn hash160(self, compressed: bool) -> Vec<u8> {
         hash160::hash160(&self.sec(compressed))
     }
 
-    pub fn address(self, compressed: bool, testnet: bool) -> String {
-        let mut h160 = self.hash160(compressed);
-        let prefix = if testnet { 0x6f } else { 0x00 };
-        h160.insert(0, prefix);
-        base58::encode_base58_checksum(&h160)
+    // P2PKH; the point at infinity has no address and panics
+    pub fn address(self, compressed: bool, network: Network) -> String {
+        PublicKey::new(self, compressed).unwrap().address(network)
     }
 }
 
@@ -175,6 +283,9 @@ impl_ops::impl_op_ex!(+ |p1: &S256Point, p2: &S256Point| -> S256Point{
 
         // case: S256Points are on the same curve and meet a special condition
         if p1 == p2 {
+            if p1.x.is_none() {
+                return p1.clone();
+            }
             let (p1_x, p1_y) = (p1.x.as_ref().unwrap(), p1.y.as_ref().unwrap());
             if p1_x.clone() == S256Field::new(BigInt::zero()) * p1_y {
                 return S256Point::new(None, None);
@@ -204,11 +315,39 @@ impl_ops::impl_op_ex!(+ |p1: &S256Point, p2: &S256Point| -> S256Point{
         }
 });
 
+impl_ops::impl_op_ex!(-|p: &S256Point| -> S256Point {
+    match (&p.x, &p.y) {
+        (Some(x), Some(y)) => S256Point::new(Some(x.clone()), Some(S256Field::new(&y.prime - &y.num))),
+        _ => p.clone(),
+    }
+});
+
+// compressed SEC, or "00" for the point at infinity, which has no SEC form
+impl ToHex for S256Point {
+    fn to_hex(&self) -> String {
+        match self.x {
+            Some(_) => hex(&self.clone().sec(true)),
+            None => "00".to_string(),
+        }
+    }
+}
+
+// either SEC form, checked to be on the curve, or "00" for the point at infinity
+impl FromHex for S256Point {
+    type Error = PublicKeyError;
+
+    fn from_hex(s: &str) -> Result<Self, PublicKeyError> {
+        if s == "00" {
+            return Ok(Self::new(None, None));
+        }
+        Ok(PublicKey::from_hex(s)?.point().clone())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::security::private_key::PrivateKey;
-    use crate::util::hex::hex;
 
     #[test]
     fn sec_not_compressed1() {
@@ -250,6 +389,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hex_test() {
+        let point = PrivateKey::new(BigInt::from(5001u32)).point;
+        let compressed = "0357a4f368868a8a6d572991e484e664810ff14c05c0fa023275251151fe0e53d1";
+        assert_eq!(point.to_hex(), compressed);
+        assert_eq!(S256Point::from_hex(compressed).unwrap(), point);
+        assert_eq!(
+            S256Point::from_hex(&hex(&point.clone().sec(false))).unwrap(),
+            point
+        );
+        assert_eq!(
+            S256Point::from_hex(&compressed[..64]),
+            Err(PublicKeyError::InvalidLength(32))
+        );
+
+        let infinity = S256Point::new(None, None);
+        assert_eq!(infinity.to_hex(), "00");
+        assert_eq!(S256Point::from_hex("00").unwrap(), infinity);
+    }
+
     #[test]
     fn sec_compressed2() {
         let prv = PrivateKey::new(BigInt::from(2019).pow(5));
@@ -275,7 +434,7 @@ mod tests {
     fn address1() {
         let prv = PrivateKey::new(BigInt::from(5002));
         assert_eq!(
-            prv.point.address(false, true),
+            prv.point.address(false, Network::Testnet3),
             "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA"
         );
     }
@@ -283,7 +442,7 @@ mod tests {
     fn address2() {
         let prv = PrivateKey::new(BigInt::from(2020).pow(5));
         assert_eq!(
-            prv.point.address(true, true),
+            prv.point.address(true, Network::Testnet3),
             "mopVkxp8UhXqRYbCYJsbeE1h1fiF64jcoH"
         );
     }
@@ -292,8 +451,74 @@ mod tests {
         let key = b"12345deadbeef";
         let prv = PrivateKey::new(BigInt::parse_bytes(key, 16).unwrap());
         assert_eq!(
-            prv.point.address(true, false),
+            prv.point.address(true, Network::Mainnet),
             "1F1Pn2y6pDb68E5nYJJeba4TLg2U7B6KF1"
         );
     }
+
+    #[test]
+    fn rmul_ct() {
+        let g = S256Point::new_g();
+        let coef = BigInt::from(2019).pow(5);
+        assert_eq!(g.clone().rmul_ct(coef.clone()), g.rmul(coef));
+    }
+
+    #[test]
+    fn rmul_ct_zero() {
+        let g = S256Point::new_g();
+        let n = g.n.clone();
+        assert_eq!(g.rmul_ct(n), S256Point::new(None, None));
+    }
+
+    #[test]
+    fn parse_compressed() {
+        let prv = PrivateKey::new(BigInt::from(2019).pow(5));
+        let sec = prv.point.clone().sec(true);
+        assert_eq!(S256Point::new(None, None).parse(sec), prv.point);
+    }
+
+    #[test]
+    fn lift_x_not_on_curve() {
+        // BIP340 test vector 5: public key not on the curve
+        let x = b"eefdea4cdb677750a420fee807eacf21eb9898ae79b9768766e4faa04a2d4a34";
+        assert_eq!(S256Point::lift_x(BigInt::parse_bytes(x, 16).unwrap()), None);
+    }
+
+    #[test]
+    fn verify_schnorr1() {
+        // BIP340 test vector 1
+        let px = b"dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659";
+        let point = S256Point::lift_x(BigInt::parse_bytes(px, 16).unwrap()).unwrap();
+        let msg = BigInt::parse_bytes(
+            b"243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+            16,
+        )
+        .unwrap();
+        let r = b"6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de3341";
+        let s = b"8906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a";
+        let sig = SchnorrSignature::new(
+            BigInt::parse_bytes(r, 16).unwrap(),
+            BigInt::parse_bytes(s, 16).unwrap(),
+        );
+        assert_eq!(true, point.verify_schnorr(&to_32_bytes(&msg), sig));
+    }
+
+    #[test]
+    fn verify_schnorr2() {
+        // BIP340 test vector 6: has_even_y(R) is false
+        let px = b"dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659";
+        let point = S256Point::lift_x(BigInt::parse_bytes(px, 16).unwrap()).unwrap();
+        let msg = BigInt::parse_bytes(
+            b"243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+            16,
+        )
+        .unwrap();
+        let r = b"fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556";
+        let s = b"3cc27944640ac607cd107ae10923d9ef7a73c643e166be5ebeafa34b1ac553e2";
+        let sig = SchnorrSignature::new(
+            BigInt::parse_bytes(r, 16).unwrap(),
+            BigInt::parse_bytes(s, 16).unwrap(),
+        );
+        assert_eq!(false, point.verify_schnorr(&to_32_bytes(&msg), sig));
+    }
 }
@@ -1 +1,13 @@
+// the arithmetic is written with explicit returns and borrowed operands throughout, and the
+// tests compare against literal booleans
+#![allow(
+    clippy::needless_return,
+    clippy::op_ref,
+    clippy::bool_assert_comparison
+)]
 
+pub mod ecc;
+pub mod network;
+pub mod security;
+pub mod transaction;
+pub mod util;
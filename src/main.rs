@@ -1,8 +1,3 @@
-mod ecc;
-mod security;
-mod transaction;
-mod util;
-
 fn main() {
     println!("Hello, world!");
 }
@@ -0,0 +1,219 @@
+// Chain parameters. The test networks share the testnet address, WIF and extended key
+// prefixes, so anything decoded from those comes back as Testnet3.
+use crate::util::hash_types::BlockHash;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet3,
+    Testnet4,
+    Signet,
+    Regtest,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnknownNetwork(pub String);
+
+impl Network {
+    pub const ALL: [Network; 5] = [
+        Network::Mainnet,
+        Network::Testnet3,
+        Network::Testnet4,
+        Network::Signet,
+        Network::Regtest,
+    ];
+
+    pub fn is_mainnet(self) -> bool {
+        self == Network::Mainnet
+    }
+
+    pub fn p2pkh_prefix(self) -> u8 {
+        if self.is_mainnet() {
+            0x00
+        } else {
+            0x6f
+        }
+    }
+
+    pub fn p2sh_prefix(self) -> u8 {
+        if self.is_mainnet() {
+            0x05
+        } else {
+            0xc4
+        }
+    }
+
+    pub fn wif_prefix(self) -> u8 {
+        if self.is_mainnet() {
+            0x80
+        } else {
+            0xef
+        }
+    }
+
+    pub fn bech32_hrp(self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet3 | Network::Testnet4 | Network::Signet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+
+    pub fn xpub_version(self) -> [u8; 4] {
+        if self.is_mainnet() {
+            [0x04, 0x88, 0xb2, 0x1e]
+        } else {
+            [0x04, 0x35, 0x87, 0xcf]
+        }
+    }
+
+    pub fn xprv_version(self) -> [u8; 4] {
+        if self.is_mainnet() {
+            [0x04, 0x88, 0xad, 0xe4]
+        } else {
+            [0x04, 0x35, 0x83, 0x94]
+        }
+    }
+
+    // start of every P2P message
+    pub fn magic(self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet3 => [0x0b, 0x11, 0x09, 0x07],
+            Network::Testnet4 => [0x1c, 0x16, 0x3f, 0x28],
+            Network::Signet => [0x0a, 0x03, 0xcf, 0x40],
+            Network::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+        }
+    }
+
+    pub fn default_port(self) -> u16 {
+        match self {
+            Network::Mainnet => 8333,
+            Network::Testnet3 => 18333,
+            Network::Testnet4 => 48333,
+            Network::Signet => 38333,
+            Network::Regtest => 18444,
+        }
+    }
+
+    pub fn genesis_hash(self) -> BlockHash {
+        let hash = match self {
+            Network::Mainnet => "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            Network::Testnet3 => "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+            Network::Testnet4 => "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043",
+            Network::Signet => "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6",
+            Network::Regtest => "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+        };
+        hash.parse().unwrap()
+    }
+
+    // first network using the prefix; see the note at the top
+    pub fn from_p2pkh_prefix(prefix: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|n| n.p2pkh_prefix() == prefix)
+    }
+
+    pub fn from_p2sh_prefix(prefix: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|n| n.p2sh_prefix() == prefix)
+    }
+
+    pub fn from_wif_prefix(prefix: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|n| n.wif_prefix() == prefix)
+    }
+
+    pub fn from_xpub_version(version: [u8; 4]) -> Option<Self> {
+        Self::ALL.into_iter().find(|n| n.xpub_version() == version)
+    }
+
+    pub fn from_xprv_version(version: [u8; 4]) -> Option<Self> {
+        Self::ALL.into_iter().find(|n| n.xprv_version() == version)
+    }
+
+    pub fn from_bech32_hrp(hrp: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|n| n.bech32_hrp() == hrp)
+    }
+
+    pub fn from_magic(magic: [u8; 4]) -> Option<Self> {
+        Self::ALL.into_iter().find(|n| n.magic() == magic)
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet3 => "testnet3",
+            Network::Testnet4 => "testnet4",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Network {
+    type Err = UnknownNetwork;
+
+    // also accepts the names bitcoind uses for -chain
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "main" | "bitcoin" => Ok(Network::Mainnet),
+            "testnet3" | "testnet" | "test" => Ok(Network::Testnet3),
+            "testnet4" => Ok(Network::Testnet4),
+            "signet" => Ok(Network::Signet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(UnknownNetwork(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters_test() {
+        assert_eq!(Network::Testnet4.p2pkh_prefix(), 0x6f);
+        assert_eq!(Network::Signet.bech32_hrp(), "tb");
+        assert_eq!(Network::Regtest.bech32_hrp(), "bcrt");
+        assert_eq!(Network::Mainnet.magic(), [0xf9, 0xbe, 0xb4, 0xd9]);
+        assert_eq!(Network::Testnet4.default_port(), 48333);
+        assert_eq!(
+            Network::Mainnet.genesis_hash().to_string(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        // every network has its own magic and genesis block
+        for (i, a) in Network::ALL.iter().enumerate() {
+            for b in &Network::ALL[i + 1..] {
+                assert_eq!(false, a.magic() == b.magic());
+                assert_eq!(false, a.genesis_hash() == b.genesis_hash());
+                assert_eq!(false, a.default_port() == b.default_port());
+            }
+        }
+    }
+
+    #[test]
+    fn lookup_test() {
+        assert_eq!(Network::from_wif_prefix(0x80), Some(Network::Mainnet));
+        assert_eq!(Network::from_wif_prefix(0xef), Some(Network::Testnet3));
+        assert_eq!(Network::from_p2sh_prefix(0x6f), None);
+        assert_eq!(Network::from_bech32_hrp("bcrt"), Some(Network::Regtest));
+        assert_eq!(
+            Network::from_magic([0x0a, 0x03, 0xcf, 0x40]),
+            Some(Network::Signet)
+        );
+    }
+
+    #[test]
+    fn from_str_test() {
+        for network in Network::ALL {
+            assert_eq!(network.to_string().parse::<Network>().unwrap(), network);
+        }
+        assert_eq!("main".parse::<Network>().unwrap(), Network::Mainnet);
+        assert_eq!(
+            "testnet5".parse::<Network>(),
+            Err(UnknownNetwork("testnet5".to_string()))
+        );
+    }
+}
@@ -1,2 +1,15 @@
+pub mod adaptor;
+pub mod bip32;
+pub mod bip38;
+pub mod bip39;
+pub mod keystore;
+pub mod musig2;
+pub mod nonce_reuse;
+pub mod pay_to_contract;
+pub mod pedersen;
 pub mod private_key;
+pub mod public_key;
+pub mod schnorr_signature;
+pub mod shamir;
 pub mod signature;
+pub mod vanity;
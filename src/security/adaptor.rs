@@ -0,0 +1,259 @@
+// Adaptor signatures: pre-signatures that become valid signatures once the
+// discrete log t of the adaptor point T = t * G is known, and reveal t when published.
+use super::private_key::PrivateKey;
+use super::schnorr_signature::SchnorrSignature;
+use super::signature::Signature;
+use crate::ecc::s256_point::S256Point;
+use crate::util::bigint::{inverse, order, random_scalar};
+use crate::util::tagged_hash::{tagged_hash, Tag};
+use num_bigint::{BigInt, Sign};
+use num_traits::Zero;
+
+// ECDSA pre-signature. r is the nonce point encrypted to the adaptor point (k * T),
+// r_a is k * G and the proof shows both share the same k.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EcdsaAdaptorSignature {
+    pub r: S256Point,
+    pub r_a: S256Point,
+    pub s: BigInt,
+    proof: DleqProof,
+}
+
+// Schnorr pre-signature. r is the final nonce point k * G + T, including the parity of y.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SchnorrAdaptorSignature {
+    pub r: S256Point,
+    pub s: BigInt,
+}
+
+// proof that log_G(a) == log_t(b)
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct DleqProof {
+    c: BigInt,
+    z: BigInt,
+}
+
+impl DleqProof {
+    fn challenge(
+        t: &S256Point,
+        a: &S256Point,
+        b: &S256Point,
+        a_nonce: &S256Point,
+        b_nonce: &S256Point,
+    ) -> BigInt {
+        let bytes: Vec<u8> = [t, a, b, a_nonce, b_nonce]
+            .iter()
+            .flat_map(|p| (*p).clone().sec(true))
+            .collect();
+        BigInt::from_bytes_be(Sign::Plus, &tagged_hash("DLEQ", &bytes)) % order()
+    }
+
+    fn prove(k: &BigInt, t: &S256Point, a: &S256Point, b: &S256Point) -> Self {
+        let w = random_scalar();
+        let a_nonce = S256Point::new_g().rmul(w.clone());
+        let b_nonce = t.clone().rmul(w.clone());
+        let c = Self::challenge(t, a, b, &a_nonce, &b_nonce);
+        let z = (w + &c * k) % order();
+        Self { c, z }
+    }
+
+    fn verify(&self, t: &S256Point, a: &S256Point, b: &S256Point) -> bool {
+        // the challenge hashes every point, and the point at infinity has no SEC form
+        if t.x.is_none() || a.x.is_none() || b.x.is_none() {
+            return false;
+        }
+        let n = order();
+        let neg_c = &n - &self.c;
+        let a_nonce = S256Point::new_g().rmul(self.z.clone()) + a.clone().rmul(neg_c.clone());
+        let b_nonce = t.clone().rmul(self.z.clone()) + b.clone().rmul(neg_c);
+        if a_nonce.x.is_none() || b_nonce.x.is_none() {
+            return false;
+        }
+        Self::challenge(t, a, b, &a_nonce, &b_nonce) == self.c
+    }
+}
+
+impl EcdsaAdaptorSignature {
+    pub fn sign(key: &PrivateKey, z: &BigInt, t: &S256Point) -> Self {
+        let n = order();
+        let k = random_scalar();
+        let r_a = S256Point::new_g().rmul(k.clone());
+        let r = t.clone().rmul(k.clone());
+        let proof = DleqProof::prove(&k, t, &r_a, &r);
+        let x = r.x.as_ref().unwrap().num.clone() % &n;
+        let s = (z + x * &key.secret) * inverse(&k) % &n;
+        Self { r, r_a, s, proof }
+    }
+
+    pub fn verify(&self, pubkey: &S256Point, z: &BigInt, t: &S256Point) -> bool {
+        let n = order();
+        if self.r.x.is_none() || self.s.is_zero() || self.s >= n || !self.proof.verify(t, &self.r_a, &self.r) {
+            return false;
+        }
+        let x = self.r.x.as_ref().unwrap().num.clone() % &n;
+        let s_inv = inverse(&self.s);
+        let u = z * &s_inv % &n;
+        let v = x * &s_inv % &n;
+        S256Point::new_g().rmul(u) + pubkey.clone().rmul(v) == self.r_a
+    }
+
+    // decrypt the pre-signature into an ordinary (low-s) signature
+    pub fn complete(&self, t: &BigInt) -> Signature {
+        let n = order();
+        let r = self.r.x.as_ref().unwrap().num.clone() % &n;
+        let mut s = &self.s * inverse(t) % &n;
+        if s > &n / BigInt::from(2u8) {
+            s = &n - s;
+        }
+        Signature::new(r, s)
+    }
+
+    // recover the adaptor secret from the published signature
+    pub fn extract(&self, sig: &Signature, t: &S256Point) -> Option<BigInt> {
+        let n = order();
+        let candidate = &self.s * inverse(&sig.s) % &n;
+        let point = S256Point::new_g().rmul(candidate.clone());
+        if &point == t {
+            Some(candidate)
+        } else if -&point == *t {
+            // complete() negated s to make it low-s
+            Some(&n - candidate)
+        } else {
+            None
+        }
+    }
+}
+
+impl SchnorrAdaptorSignature {
+    pub fn sign(key: &PrivateKey, msg: &[u8], t: &S256Point) -> Self {
+        let n = order();
+        let d = if key.point.has_even_y() {
+            key.secret.clone()
+        } else {
+            &n - &key.secret
+        };
+        let k = random_scalar();
+        let r = S256Point::new_g().rmul(k.clone()) + t;
+        let e = Self::challenge(&r, &key.point, msg);
+        // when R has an odd y the final signature uses -R, so the nonce is negated
+        let k = if r.has_even_y() { k } else { &n - k };
+        let s = (k + e * d) % &n;
+        Self { r, s }
+    }
+
+    fn challenge(r: &S256Point, pubkey: &S256Point, msg: &[u8]) -> BigInt {
+        let mut bytes = r.xonly();
+        bytes.extend(pubkey.xonly());
+        bytes.extend(msg);
+        BigInt::from_bytes_be(Sign::Plus, &Tag::Bip340Challenge.hash(&bytes)) % order()
+    }
+
+    pub fn verify(&self, pubkey: &S256Point, msg: &[u8], t: &S256Point) -> bool {
+        let n = order();
+        if self.r.x.is_none() || self.s >= n {
+            return false;
+        }
+        let p = match pubkey.x.as_ref().and_then(|x| S256Point::lift_x(x.num.clone())) {
+            Some(p) => p,
+            None => return false,
+        };
+        let e = Self::challenge(&self.r, &p, msg);
+        let r_nonce = &self.r + -t;
+        let r_nonce = if self.r.has_even_y() {
+            r_nonce
+        } else {
+            -r_nonce
+        };
+        S256Point::new_g().rmul(self.s.clone()) == r_nonce + p.rmul(e)
+    }
+
+    pub fn complete(&self, t: &BigInt) -> SchnorrSignature {
+        let n = order();
+        let s = if self.r.has_even_y() {
+            (&self.s + t) % &n
+        } else {
+            (&self.s - t % &n + &n) % &n
+        };
+        SchnorrSignature::new(self.r.x.as_ref().unwrap().num.clone(), s)
+    }
+
+    pub fn extract(&self, sig: &SchnorrSignature, t: &S256Point) -> Option<BigInt> {
+        let n = order();
+        let candidate = if self.r.has_even_y() {
+            (&sig.s - &self.s + &n) % &n
+        } else {
+            (&self.s - &sig.s + &n) % &n
+        };
+        if &S256Point::new_g().rmul(candidate.clone()) == t {
+            Some(candidate)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adaptor_secret() -> (BigInt, S256Point) {
+        let t = BigInt::parse_bytes(b"deadbeef54321", 16).unwrap();
+        let point = S256Point::new_g().rmul(t.clone());
+        (t, point)
+    }
+
+    fn z() -> BigInt {
+        let bytes_z = b"bc62d4b80d9e36da29c16c5d4d9f11731f36052c72401a76c23c0fb5a9b74423";
+        BigInt::parse_bytes(bytes_z, 16).unwrap()
+    }
+
+    #[test]
+    fn ecdsa_adaptor_verify() {
+        let prv = PrivateKey::new(BigInt::from(2021).pow(5));
+        let (_, t_point) = adaptor_secret();
+        let presig = EcdsaAdaptorSignature::sign(&prv, &z(), &t_point);
+        assert_eq!(true, presig.verify(&prv.point, &z(), &t_point));
+        assert_eq!(false, presig.verify(&prv.point, &(z() + 1), &t_point));
+        assert_eq!(false, presig.verify(&prv.point, &z(), &S256Point::new_g()));
+        let infinity = S256Point::new(None, None);
+        assert_eq!(false, presig.verify(&prv.point, &z(), &infinity));
+    }
+
+    #[test]
+    fn ecdsa_adaptor_complete_and_extract() {
+        let prv = PrivateKey::new(BigInt::from(2021).pow(5));
+        let (t, t_point) = adaptor_secret();
+        let presig = EcdsaAdaptorSignature::sign(&prv, &z(), &t_point);
+        let sig = presig.complete(&t);
+        assert_eq!(true, prv.point.clone().verify(z(), sig.clone()));
+        assert_eq!(presig.extract(&sig, &t_point), Some(t));
+    }
+
+    #[test]
+    fn schnorr_adaptor_verify() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let (_, t_point) = adaptor_secret();
+        let presig = SchnorrAdaptorSignature::sign(&prv, b"atomic swap", &t_point);
+        assert_eq!(true, presig.verify(&prv.point, b"atomic swap", &t_point));
+        assert_eq!(false, presig.verify(&prv.point, b"atomic swap!", &t_point));
+        assert_eq!(
+            false,
+            presig.verify(&prv.point, b"atomic swap", &S256Point::new_g())
+        );
+        let infinity = S256Point::new(None, None);
+        assert_eq!(false, presig.verify(&infinity, b"atomic swap", &t_point));
+    }
+
+    #[test]
+    fn schnorr_adaptor_complete_and_extract() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let (t, t_point) = adaptor_secret();
+        let presig = SchnorrAdaptorSignature::sign(&prv, b"atomic swap", &t_point);
+        let sig = presig.complete(&t);
+        assert_eq!(
+            true,
+            prv.point.clone().verify_schnorr(b"atomic swap", sig.clone())
+        );
+        assert_eq!(presig.extract(&sig, &t_point), Some(t));
+    }
+}
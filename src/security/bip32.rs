@@ -0,0 +1,515 @@
+// BIP32 hierarchical deterministic keys
+use super::private_key::PrivateKey;
+use crate::ecc::s256_point::S256Point;
+use crate::network::Network;
+use crate::util::base58::{decode_base58_checksum, encode_base58_checksum, Base58Error};
+use crate::util::bigint::{order, to_32_bytes};
+use crate::util::hash_types::Hash160;
+use crate::util::hmac_sha512::hmac_sha512;
+use num_bigint::{BigInt, Sign};
+use num_traits::Zero;
+use std::fmt;
+use std::str::FromStr;
+
+// child numbers at or above this are hardened
+pub const HARDENED: u32 = 0x8000_0000;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Bip32Error {
+    // seeds must be between 16 and 64 bytes
+    InvalidSeedLength(usize),
+    // the derived key is zero, infinity or I_L >= n; the caller should move on to the next index
+    InvalidChild,
+    // hardened children can't be derived from an extended public key
+    HardenedFromPublic,
+    InvalidPath(String),
+    Base58(Base58Error),
+    InvalidLength(usize),
+    UnknownVersion([u8; 4]),
+    InvalidPrivateKey,
+    InvalidPublicKey,
+    // a master key (depth 0) has a parent fingerprint or child number
+    InvalidDepth,
+    // the parent is already at depth 255, the most a single byte can hold
+    MaxDepth,
+}
+
+impl From<Base58Error> for Bip32Error {
+    fn from(e: Base58Error) -> Self {
+        Bip32Error::Base58(e)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DerivationPath {
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExtendedPrivKey {
+    pub network: Network,
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub private_key: PrivateKey,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExtendedPubKey {
+    pub network: Network,
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub public_key: S256Point,
+}
+
+fn fingerprint(point: &S256Point) -> [u8; 4] {
+    let hash = Hash160::hash(&point.clone().sec(true));
+    hash.as_bytes()[..4].try_into().unwrap()
+}
+
+fn split(i: [u8; 64]) -> (BigInt, [u8; 32]) {
+    let il = BigInt::from_bytes_be(Sign::Plus, &i[..32]);
+    (il, i[32..].try_into().unwrap())
+}
+
+// version || depth || parent fingerprint || child number || chain code || key
+fn encode(
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: &[u8; 4],
+    child_number: u32,
+    chain_code: &[u8; 32],
+    key: &[u8],
+) -> String {
+    let mut bytes = version.to_vec();
+    bytes.push(depth);
+    bytes.extend(parent_fingerprint);
+    bytes.extend(child_number.to_be_bytes());
+    bytes.extend(chain_code);
+    bytes.extend(key);
+    encode_base58_checksum(&bytes)
+}
+
+// fields shared by xprv and xpub
+struct Decoded {
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    key: [u8; 33],
+}
+
+fn decode(s: &str) -> Result<Decoded, Bip32Error> {
+    let payload = decode_base58_checksum(s)?;
+    if payload.len() != 78 {
+        return Err(Bip32Error::InvalidLength(payload.len()));
+    }
+
+    let decoded = Decoded {
+        version: payload[0..4].try_into().unwrap(),
+        depth: payload[4],
+        parent_fingerprint: payload[5..9].try_into().unwrap(),
+        child_number: u32::from_be_bytes(payload[9..13].try_into().unwrap()),
+        chain_code: payload[13..45].try_into().unwrap(),
+        key: payload[45..78].try_into().unwrap(),
+    };
+    if decoded.depth == 0 && (decoded.parent_fingerprint != [0; 4] || decoded.child_number != 0) {
+        return Err(Bip32Error::InvalidDepth);
+    }
+    Ok(decoded)
+}
+
+impl ExtendedPrivKey {
+    pub fn new_master(seed: &[u8], network: Network) -> Result<Self, Bip32Error> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(Bip32Error::InvalidSeedLength(seed.len()));
+        }
+        let (il, chain_code) = split(hmac_sha512(b"Bitcoin seed", seed));
+        if il.is_zero() || il >= order() {
+            return Err(Bip32Error::InvalidChild);
+        }
+        Ok(Self {
+            network,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+            chain_code,
+            private_key: PrivateKey::new(il),
+        })
+    }
+
+    pub fn derive_child(&self, index: u32) -> Result<Self, Bip32Error> {
+        let depth = self.depth.checked_add(1).ok_or(Bip32Error::MaxDepth)?;
+        let mut data = if index >= HARDENED {
+            let mut data = vec![0x00];
+            data.extend(to_32_bytes(&self.private_key.secret));
+            data
+        } else {
+            self.private_key.point.clone().sec(true)
+        };
+        data.extend(index.to_be_bytes());
+
+        let (il, chain_code) = split(hmac_sha512(&self.chain_code, &data));
+        let private_key = self
+            .private_key
+            .add_tweak(&il)
+            .map_err(|_| Bip32Error::InvalidChild)?;
+        Ok(Self {
+            network: self.network,
+            depth,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            chain_code,
+            private_key,
+        })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        path.indices
+            .iter()
+            .try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+
+    pub fn extended_pub_key(&self) -> ExtendedPubKey {
+        ExtendedPubKey {
+            network: self.network,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            public_key: self.private_key.point.clone(),
+        }
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        fingerprint(&self.private_key.point)
+    }
+
+    // xprv or tprv
+    pub fn base58(&self) -> String {
+        let version = self.network.xprv_version();
+        let mut key = vec![0x00];
+        key.extend(to_32_bytes(&self.private_key.secret));
+        encode(
+            version,
+            self.depth,
+            &self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            &key,
+        )
+    }
+
+    pub fn from_base58(s: &str) -> Result<Self, Bip32Error> {
+        let decoded = decode(s)?;
+        let network = Network::from_xprv_version(decoded.version)
+            .ok_or(Bip32Error::UnknownVersion(decoded.version))?;
+        if decoded.key[0] != 0x00 {
+            return Err(Bip32Error::InvalidPrivateKey);
+        }
+        let private_key = PrivateKey::from_bytes(decoded.key[1..].try_into().unwrap())
+            .map_err(|_| Bip32Error::InvalidPrivateKey)?;
+        Ok(Self {
+            network,
+            depth: decoded.depth,
+            parent_fingerprint: decoded.parent_fingerprint,
+            child_number: decoded.child_number,
+            chain_code: decoded.chain_code,
+            private_key,
+        })
+    }
+}
+
+impl ExtendedPubKey {
+    pub fn derive_child(&self, index: u32) -> Result<Self, Bip32Error> {
+        if index >= HARDENED {
+            return Err(Bip32Error::HardenedFromPublic);
+        }
+        let depth = self.depth.checked_add(1).ok_or(Bip32Error::MaxDepth)?;
+        let mut data = self.public_key.clone().sec(true);
+        data.extend(index.to_be_bytes());
+
+        let (il, chain_code) = split(hmac_sha512(&self.chain_code, &data));
+        let public_key = self
+            .public_key
+            .add_exp_tweak(&il)
+            .map_err(|_| Bip32Error::InvalidChild)?;
+        Ok(Self {
+            network: self.network,
+            depth,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            chain_code,
+            public_key,
+        })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        path.indices
+            .iter()
+            .try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        fingerprint(&self.public_key)
+    }
+
+    // xpub or tpub
+    pub fn base58(&self) -> String {
+        let version = self.network.xpub_version();
+        encode(
+            version,
+            self.depth,
+            &self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            &self.public_key.clone().sec(true),
+        )
+    }
+
+    pub fn from_base58(s: &str) -> Result<Self, Bip32Error> {
+        let decoded = decode(s)?;
+        let network = Network::from_xpub_version(decoded.version)
+            .ok_or(Bip32Error::UnknownVersion(decoded.version))?;
+        let public_key = match decoded.key[0] {
+            0x02 | 0x03 => S256Point::lift_x(BigInt::from_bytes_be(Sign::Plus, &decoded.key[1..]))
+                .ok_or(Bip32Error::InvalidPublicKey)?,
+            _ => return Err(Bip32Error::InvalidPublicKey),
+        };
+        let public_key = if decoded.key[0] == 0x02 {
+            public_key
+        } else {
+            -public_key
+        };
+        Ok(Self {
+            network,
+            depth: decoded.depth,
+            parent_fingerprint: decoded.parent_fingerprint,
+            child_number: decoded.child_number,
+            chain_code: decoded.chain_code,
+            public_key,
+        })
+    }
+}
+
+// accepts "m/84'/0'/0'/0/5", with ', h or H marking hardened indices
+impl FromStr for DerivationPath {
+    type Err = Bip32Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(Bip32Error::InvalidPath(s.to_string()));
+        }
+        let indices = parts
+            .map(|part| {
+                let (digits, hardened) = match part.strip_suffix(['\'', 'h', 'H']) {
+                    Some(digits) => (digits, true),
+                    None => (part, false),
+                };
+                match digits.parse::<u32>() {
+                    Ok(index) if index < HARDENED && digits.chars().all(|c| c.is_ascii_digit()) => {
+                        Ok(if hardened { index + HARDENED } else { index })
+                    }
+                    _ => Err(Bip32Error::InvalidPath(s.to_string())),
+                }
+            })
+            .collect::<Result<Vec<u32>, Bip32Error>>()?;
+        Ok(Self { indices })
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for index in &self.indices {
+            if *index >= HARDENED {
+                write!(f, "/{}'", index - HARDENED)?;
+            } else {
+                write!(f, "/{}", index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex::FromHex;
+
+    fn check(master: &ExtendedPrivKey, path: &str, xprv: &str, xpub: &str) {
+        let key = master.derive_path(&path.parse().unwrap()).unwrap();
+        assert_eq!(key.base58(), xprv);
+        assert_eq!(key.extended_pub_key().base58(), xpub);
+        assert_eq!(ExtendedPrivKey::from_base58(xprv).unwrap(), key);
+        assert_eq!(
+            ExtendedPubKey::from_base58(xpub).unwrap(),
+            key.extended_pub_key()
+        );
+    }
+
+    #[test]
+    fn bip32_vector1() {
+        let master = ExtendedPrivKey::new_master(
+            &Vec::from_hex("000102030405060708090a0b0c0d0e0f").unwrap(),
+            Network::Mainnet,
+        )
+        .unwrap();
+        check(
+            &master,
+            "m",
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+        );
+        check(
+            &master,
+            "m/0'/1",
+            "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+            "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+        );
+        check(
+            &master,
+            "m/0h/1/2h/2/1000000000",
+            "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+            "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+        );
+    }
+
+    #[test]
+    fn bip32_vector2() {
+        let master = ExtendedPrivKey::new_master(
+            &Vec::from_hex("fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542").unwrap(),
+            Network::Mainnet,
+        )
+        .unwrap();
+        check(
+            &master,
+            "m/0/2147483647'",
+            "xprv9wSp6B7kry3Vj9m1zSnLvN3xH8RdsPP1Mh7fAaR7aRLcQMKTR2vidYEeEg2mUCTAwCd6vnxVrcjfy2kRgVsFawNzmjuHc2YmYRmagcEPdU9",
+            "xpub6ASAVgeehLbnwdqV6UKMHVzgqAG8Gr6riv3Fxxpj8ksbH9ebxaEyBLZ85ySDhKiLDBrQSARLq1uNRts8RuJiHjaDMBU4Zn9h8LZNnBC5y4a",
+        );
+    }
+
+    #[test]
+    fn bip32_vector3() {
+        // leading zeros of the private key must be kept
+        let master = ExtendedPrivKey::new_master(
+            &Vec::from_hex("4b381541583be4423346c643850da4b320e46a87ae3d2a4e6da11eba819cd4acba45d239319ac14f863b8d5ab5a0d0c64d2e8a1e7d1457df2e5a3c51c73235be").unwrap(),
+            Network::Mainnet,
+        )
+        .unwrap();
+        check(
+            &master,
+            "m/0'",
+            "xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L",
+            "xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y",
+        );
+    }
+
+    #[test]
+    fn public_derivation() {
+        // m/0'/1/2' -> m/0'/1/2'/2 of vector 1
+        let xpub = ExtendedPubKey::from_base58("xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5").unwrap();
+        assert_eq!(
+            xpub.derive_child(2).unwrap().base58(),
+            "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV"
+        );
+        assert_eq!(
+            xpub.derive_child(2 + HARDENED),
+            Err(Bip32Error::HardenedFromPublic)
+        );
+    }
+
+    #[test]
+    fn fingerprint_test() {
+        let master = ExtendedPrivKey::new_master(
+            &Vec::from_hex("000102030405060708090a0b0c0d0e0f").unwrap(),
+            Network::Testnet3,
+        )
+        .unwrap();
+        assert_eq!(master.fingerprint(), [0x34, 0x42, 0x19, 0x3e]);
+        assert_eq!(
+            master.extended_pub_key().fingerprint(),
+            master.fingerprint()
+        );
+        assert_eq!(&master.base58()[..4], "tprv");
+        assert_eq!(&master.extended_pub_key().base58()[..4], "tpub");
+    }
+
+    #[test]
+    fn derivation_path_parse() {
+        let path: DerivationPath = "m/84'/0'/0'/0/5".parse().unwrap();
+        assert_eq!(path.indices, vec![84 + HARDENED, HARDENED, HARDENED, 0, 5]);
+        assert_eq!(path.to_string(), "m/84'/0'/0'/0/5");
+        assert_eq!("m".parse::<DerivationPath>().unwrap().indices, vec![]);
+        for invalid in ["", "84'/0", "m/", "m/x", "m/-1", "m/+1", "m/2147483648"] {
+            assert_eq!(
+                invalid.parse::<DerivationPath>(),
+                Err(Bip32Error::InvalidPath(invalid.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn from_base58_invalid() {
+        // from BIP32 test vector 5
+        assert_eq!(
+            ExtendedPrivKey::from_base58("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"),
+            Err(Bip32Error::UnknownVersion(Network::Mainnet.xpub_version()))
+        );
+        assert_eq!(
+            ExtendedPubKey::from_base58("xpub661MyMwAqRbcEYS8w7XLSVeEsBXy79zSzH1J8vCdxAZningWLdN3zgtU6LBpB85b3D2yc8sfvZU521AAwdZafEz7mnzBBsz4wKY5fTtTQBm"),
+            Err(Bip32Error::InvalidPublicKey)
+        );
+        assert_eq!(
+            ExtendedPrivKey::from_base58("xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFGTQQD3dC4H2D5GBj7vWvSQaaBv5cxi9gafk7NF3pnBju6dwKvH"),
+            Err(Bip32Error::InvalidPrivateKey)
+        );
+        assert_eq!(
+            ExtendedPrivKey::from_base58("xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHj"),
+            Err(Bip32Error::Base58(Base58Error::InvalidChecksum))
+        );
+
+        let mut payload = Network::Mainnet.xprv_version().to_vec();
+        payload.push(0);
+        payload.extend([0x00, 0x00, 0x00, 0x01]);
+        payload.extend([0; 4]);
+        payload.extend([0x01; 32]);
+        payload.push(0x00);
+        payload.extend([0x01; 32]);
+        assert_eq!(
+            ExtendedPrivKey::from_base58(&encode_base58_checksum(&payload)),
+            Err(Bip32Error::InvalidDepth)
+        );
+        assert_eq!(
+            ExtendedPrivKey::from_base58(&encode_base58_checksum(&payload[..77])),
+            Err(Bip32Error::InvalidLength(77))
+        );
+    }
+
+    #[test]
+    fn invalid_seed() {
+        assert_eq!(
+            ExtendedPrivKey::new_master(&[0; 15], Network::Mainnet),
+            Err(Bip32Error::InvalidSeedLength(15))
+        );
+    }
+
+    #[test]
+    fn max_depth() {
+        let mut key = ExtendedPrivKey::new_master(&[0x01; 16], Network::Mainnet).unwrap();
+        key.depth = 254;
+        let child = key.derive_child(0).unwrap();
+        assert_eq!(child.depth, 255);
+        assert_eq!(child.derive_child(0), Err(Bip32Error::MaxDepth));
+        assert_eq!(
+            child.extended_pub_key().derive_child(0),
+            Err(Bip32Error::MaxDepth)
+        );
+    }
+}
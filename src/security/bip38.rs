@@ -0,0 +1,284 @@
+// BIP38 passphrase-protected private keys
+use super::private_key::PrivateKey;
+use super::public_key::PublicKey;
+use crate::ecc::s256_point::S256Point;
+use crate::network::Network;
+use crate::util::base58::{decode_base58_checksum, encode_base58_checksum, Base58Error};
+use crate::util::bigint::to_32_bytes;
+use crate::util::hash256::hash256;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::Aes256;
+use num_bigint::{BigInt, Sign};
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Bip38Error {
+    Base58(Base58Error),
+    InvalidLength(usize),
+    InvalidPrefix([u8; 2]),
+    InvalidFlag(u8),
+    // the decrypted key does not match the address hash
+    WrongPassphrase,
+}
+
+impl From<Base58Error> for Bip38Error {
+    fn from(e: Base58Error) -> Self {
+        Bip38Error::Base58(e)
+    }
+}
+
+const NON_EC_MULTIPLY: [u8; 2] = [0x01, 0x42];
+const EC_MULTIPLY: [u8; 2] = [0x01, 0x43];
+
+const FLAG_NON_EC_MULTIPLY: u8 = 0xc0;
+const FLAG_COMPRESSED: u8 = 0x20;
+const FLAG_LOT_SEQUENCE: u8 = 0x04;
+
+fn scrypt(password: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32, len: usize) -> Vec<u8> {
+    let params = scrypt::Params::new(log_n, r, p, len).unwrap();
+    let mut output = vec![0u8; len];
+    scrypt::scrypt(password, salt, &params, &mut output).unwrap();
+    output
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(x, y)| x ^ y).collect()
+}
+
+fn aes_encrypt(key: &[u8], block: &[u8]) -> Vec<u8> {
+    let mut block = GenericArray::clone_from_slice(block);
+    Aes256::new(GenericArray::from_slice(key)).encrypt_block(&mut block);
+    block.to_vec()
+}
+
+fn aes_decrypt(key: &[u8], block: &[u8]) -> Vec<u8> {
+    let mut block = GenericArray::clone_from_slice(block);
+    Aes256::new(GenericArray::from_slice(key)).decrypt_block(&mut block);
+    block.to_vec()
+}
+
+// first 4 bytes of hash256 of the mainnet P2PKH address
+fn address_hash(pubkey: &PublicKey) -> Vec<u8> {
+    hash256(pubkey.address(Network::Mainnet).as_bytes())[..4].to_vec()
+}
+
+impl PrivateKey {
+    // non-EC-multiply mode; the result starts with "6P". The passphrase is NFC-normalized
+    pub fn encrypt_bip38(&self, passphrase: &str, compressed: bool) -> String {
+        let passphrase: String = passphrase.nfc().collect();
+        let address_hash = address_hash(&self.public_key(compressed));
+        let derived = scrypt(passphrase.as_bytes(), &address_hash, 14, 8, 8, 64);
+        let (half1, half2) = derived.split_at(32);
+        let secret = to_32_bytes(&self.secret);
+
+        let mut flag = FLAG_NON_EC_MULTIPLY;
+        if compressed {
+            flag |= FLAG_COMPRESSED;
+        }
+        let mut bytes = NON_EC_MULTIPLY.to_vec();
+        bytes.push(flag);
+        bytes.extend(&address_hash);
+        bytes.extend(aes_encrypt(half2, &xor(&secret[..16], &half1[..16])));
+        bytes.extend(aes_encrypt(half2, &xor(&secret[16..], &half1[16..])));
+        encode_base58_checksum(&bytes)
+    }
+
+    // returns (key, compressed) for both the non-EC-multiply and EC-multiply modes
+    pub fn decrypt_bip38(s: &str, passphrase: &str) -> Result<(Self, bool), Bip38Error> {
+        let passphrase: String = passphrase.nfc().collect();
+        let payload = decode_base58_checksum(s)?;
+        if payload.len() != 39 {
+            return Err(Bip38Error::InvalidLength(payload.len()));
+        }
+
+        let prefix = [payload[0], payload[1]];
+        let flag = payload[2];
+        let compressed = flag & FLAG_COMPRESSED != 0;
+        let expected_hash = &payload[3..7];
+        let secret = match prefix {
+            NON_EC_MULTIPLY => {
+                if flag & !FLAG_COMPRESSED != FLAG_NON_EC_MULTIPLY {
+                    return Err(Bip38Error::InvalidFlag(flag));
+                }
+                decrypt_non_ec_multiply(&payload, &passphrase)
+            }
+            EC_MULTIPLY => {
+                if flag & !(FLAG_COMPRESSED | FLAG_LOT_SEQUENCE) != 0 {
+                    return Err(Bip38Error::InvalidFlag(flag));
+                }
+                decrypt_ec_multiply(&payload, &passphrase)
+            }
+            _ => return Err(Bip38Error::InvalidPrefix(prefix)),
+        };
+
+        let n = S256Point::new(None, None).n;
+        if secret == BigInt::from(0u8) || secret >= n {
+            return Err(Bip38Error::WrongPassphrase);
+        }
+        let key = Self::new(secret);
+        if address_hash(&key.public_key(compressed)) != expected_hash {
+            return Err(Bip38Error::WrongPassphrase);
+        }
+        Ok((key, compressed))
+    }
+}
+
+fn decrypt_non_ec_multiply(payload: &[u8], passphrase: &str) -> BigInt {
+    let derived = scrypt(passphrase.as_bytes(), &payload[3..7], 14, 8, 8, 64);
+    let (half1, half2) = derived.split_at(32);
+    let mut secret = xor(&aes_decrypt(half2, &payload[7..23]), &half1[..16]);
+    secret.extend(xor(&aes_decrypt(half2, &payload[23..39]), &half1[16..]));
+    BigInt::from_bytes_be(Sign::Plus, &secret)
+}
+
+// the key was generated by a third party from our intermediate code:
+// secret = passfactor * hash256(seedb)
+fn decrypt_ec_multiply(payload: &[u8], passphrase: &str) -> BigInt {
+    let n = S256Point::new(None, None).n;
+    let address_hash = &payload[3..7];
+    let owner_entropy = &payload[7..15];
+
+    // with lot and sequence numbers only the first 4 bytes are the owner salt
+    let owner_salt = if payload[2] & FLAG_LOT_SEQUENCE != 0 {
+        &owner_entropy[..4]
+    } else {
+        owner_entropy
+    };
+    let prefactor = scrypt(passphrase.as_bytes(), owner_salt, 14, 8, 8, 32);
+    let passfactor = if payload[2] & FLAG_LOT_SEQUENCE != 0 {
+        let mut bytes = prefactor;
+        bytes.extend(owner_entropy);
+        hash256(&bytes)
+    } else {
+        prefactor
+    };
+    let passfactor = BigInt::from_bytes_be(Sign::Plus, &passfactor);
+    let passpoint = S256Point::new_g().rmul(passfactor.clone()).sec(true);
+
+    let mut salt = address_hash.to_vec();
+    salt.extend(owner_entropy);
+    let derived = scrypt(&passpoint, &salt, 10, 1, 1, 64);
+    let (half1, half2) = derived.split_at(32);
+
+    // encryptedpart2 holds the second half of encryptedpart1 and the last 8 bytes of seedb
+    let part2 = xor(&aes_decrypt(half2, &payload[23..39]), &half1[16..]);
+    let mut encrypted_part1 = payload[15..23].to_vec();
+    encrypted_part1.extend(&part2[..8]);
+    let mut seedb = xor(&aes_decrypt(half2, &encrypted_part1), &half1[..16]);
+    seedb.extend(&part2[8..]);
+
+    let factorb = BigInt::from_bytes_be(Sign::Plus, &hash256(&seedb));
+    passfactor * factorb % n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(s: &str) -> PrivateKey {
+        PrivateKey::from_hex(s).unwrap()
+    }
+
+    #[test]
+    fn encrypt_no_compression() {
+        let prv = key("cbf4b9f70470856bb4f40f80b87edb90865997ffee6df315ab166d713af433a5");
+        assert_eq!(
+            prv.encrypt_bip38("TestingOneTwoThree", false),
+            "6PRVWUbkzzsbcVac2qwfssoUJAN1Xhrg6bNk8J7Nzm5H7kxEbn2Nh2ZoGg"
+        );
+    }
+
+    #[test]
+    fn encrypt_compression() {
+        let prv = key("09c2686880095b1a4c249ee3ac4eea8a014f11e6f986d0b5025ac1f39afbd9ae");
+        assert_eq!(
+            prv.encrypt_bip38("Satoshi", true),
+            "6PYLtMnXvfG3oJde97zRyLYFZCYizPU5T3LwgdYJz1fRhh16bU7u6PPmY7"
+        );
+    }
+
+    #[test]
+    fn decrypt_non_ec_multiply() {
+        let (prv, compressed) = PrivateKey::decrypt_bip38(
+            "6PRNFFkZc2NZ6dJqFfhRoFNMR9Lnyj7dYGrzdgXXVMXcxoKTePPX1dWByq",
+            "Satoshi",
+        )
+        .unwrap();
+        assert_eq!(
+            prv.wif(compressed, Network::Mainnet),
+            "5HtasZ6ofTHP6HCwTqTkLDuLQisYPah7aUnSKfC7h4hMUVw2gi5"
+        );
+    }
+
+    #[test]
+    fn unicode_passphrase() {
+        // GREEK UPSILON WITH HOOK, COMBINING ACUTE ACCENT, NULL, DESERET CAPITAL LETTER LONG I,
+        // PILE OF POO; the first two become U+03D3 under NFC
+        let passphrase = "\u{03D2}\u{0301}\u{0000}\u{10400}\u{1F4A9}";
+        let encrypted = "6PRW5o9FLp4gJDDVqJQKJFTpMvdsSGJxMYHtHaQBF3ooa8mwD69bapcDQn";
+        let (prv, compressed) = PrivateKey::decrypt_bip38(encrypted, passphrase).unwrap();
+        assert_eq!(prv.encrypt_bip38(passphrase, false), encrypted);
+        assert_eq!(
+            PrivateKey::decrypt_bip38(encrypted, "\u{03D3}\u{0000}\u{10400}\u{1F4A9}"),
+            Ok((prv.clone(), false))
+        );
+        assert_eq!(
+            prv.wif(compressed, Network::Mainnet),
+            "5Jajm8eQ22H3pGWLEVCXyvND8dQZhiQhoLJNKjYXk9roUFTMSZ4"
+        );
+    }
+
+    #[test]
+    fn decrypt_ec_multiply() {
+        let (prv, compressed) = PrivateKey::decrypt_bip38(
+            "6PfQu77ygVyJLZjfvMLyhLMQbYnu5uguoJJ4kMCLqWwPEdfpwANVS76gTX",
+            "TestingOneTwoThree",
+        )
+        .unwrap();
+        assert_eq!(compressed, false);
+        assert_eq!(
+            prv,
+            key("a43a940577f4e97f5c4d39eb14ff083a98187c64ea7c99ef7ce460833959a519")
+        );
+    }
+
+    #[test]
+    fn decrypt_ec_multiply_lot_sequence() {
+        let (prv, compressed) = PrivateKey::decrypt_bip38(
+            "6PgNBNNzDkKdhkT6uJntUXwwzQV8Rr2tZcbkDcuC9DZRsS6AtHts4Ypo1j",
+            "MOLON LABE",
+        )
+        .unwrap();
+        assert_eq!(compressed, false);
+        assert_eq!(
+            prv.point.address(false, Network::Mainnet),
+            "1Jscj8ALrYu2y9TD8NrpvDBugPedmbj4Yh"
+        );
+    }
+
+    #[test]
+    fn decrypt_invalid() {
+        assert_eq!(
+            PrivateKey::decrypt_bip38(
+                "6PRNFFkZc2NZ6dJqFfhRoFNMR9Lnyj7dYGrzdgXXVMXcxoKTePPX1dWByq",
+                "Nakamoto"
+            ),
+            Err(Bip38Error::WrongPassphrase)
+        );
+        assert_eq!(
+            PrivateKey::decrypt_bip38(
+                "6PRNFFkZc2NZ6dJqFfhRoFNMR9Lnyj7dYGrzdgXXVMXcxoKTePPX1dWByQ",
+                "Satoshi"
+            ),
+            Err(Bip38Error::Base58(Base58Error::InvalidChecksum))
+        );
+        assert_eq!(
+            PrivateKey::decrypt_bip38(
+                "5HtasZ6ofTHP6HCwTqTkLDuLQisYPah7aUnSKfC7h4hMUVw2gi5",
+                "Satoshi"
+            ),
+            Err(Bip38Error::InvalidLength(33))
+        );
+    }
+}
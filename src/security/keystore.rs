@@ -0,0 +1,531 @@
+// Password-encrypted keystore for private keys and seeds.
+//
+// File layout (integers are big-endian):
+//   magic "PBKS" | version (1) | scrypt log_n (1) | r (4) | p (4) | salt (16) | nonce (12)
+//   | ChaCha20-Poly1305 ciphertext of the entries
+// The header is authenticated as associated data, so any change to the file fails to unlock.
+// Entries: count (4), then for each: kind (1) | label length (2) | label | created (8)
+//   | secret length (1) | secret
+use super::private_key::PrivateKey;
+use crate::util::bigint::{order, to_32_bytes};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use num_bigint::Sign;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::fs;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 4] = b"PBKS";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 4 + 1 + 1 + 4 + 4 + 16 + 12;
+
+// upper bounds on the header's scrypt parameters, checked before running it: scrypt needs
+// 128 * r * 2^log_n bytes of memory and p times that much work
+const MAX_LOG_N: u8 = 20;
+const MAX_R_TIMES_P: u64 = 16;
+
+const KIND_KEY: u8 = 0;
+const KIND_SEED: u8 = 1;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum KeystoreError {
+    Io(io::ErrorKind),
+    // not a keystore, or the entries are malformed
+    InvalidFormat,
+    UnsupportedVersion(u8),
+    // wrong password, or the file was modified
+    DecryptionFailed,
+    DuplicateLabel(String),
+    NotFound(String),
+    // the private key is 0 or not less than the curve order n, so it could not be loaded again
+    InvalidKey,
+}
+
+impl From<io::Error> for KeystoreError {
+    fn from(e: io::Error) -> Self {
+        KeystoreError::Io(e.kind())
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Secret {
+    Key(PrivateKey),
+    Seed(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Entry {
+    pub label: String,
+    // unix time in seconds
+    pub created: u64,
+    pub secret: Secret,
+}
+
+// scrypt cost parameters, stored in the header so they can be raised later
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+// an unlocked keystore; the password-derived key is kept so it can be saved again
+pub struct Keystore {
+    entries: Vec<Entry>,
+    params: KdfParams,
+    salt: [u8; 16],
+    key: [u8; 32],
+}
+
+fn derive_key(password: &str, salt: &[u8], params: KdfParams) -> Result<[u8; 32], KeystoreError> {
+    if params.log_n > MAX_LOG_N || params.r as u64 * params.p as u64 > MAX_R_TIMES_P {
+        return Err(KeystoreError::InvalidFormat);
+    }
+    let scrypt_params = scrypt::Params::new(params.log_n, params.r, params.p, 32)
+        .map_err(|_| KeystoreError::InvalidFormat)?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(password.as_bytes(), salt, &scrypt_params, &mut key)
+        .map_err(|_| KeystoreError::InvalidFormat)?;
+    Ok(key)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// reads the entries back, failing on anything truncated or out of range
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], KeystoreError> {
+        if self.bytes.len() < len {
+            return Err(KeystoreError::InvalidFormat);
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, KeystoreError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, KeystoreError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, KeystoreError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, KeystoreError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+// labels longer than 65535 bytes and secrets longer than 255 don't fit their length fields
+fn encode_entries(entries: &[Entry]) -> Result<Vec<u8>, KeystoreError> {
+    let count = u32::try_from(entries.len()).map_err(|_| KeystoreError::InvalidFormat)?;
+    let mut bytes = count.to_be_bytes().to_vec();
+    for entry in entries {
+        let (kind, secret) = match &entry.secret {
+            Secret::Key(key) => (KIND_KEY, to_32_bytes(&key.secret)),
+            Secret::Seed(seed) => (KIND_SEED, seed.clone()),
+        };
+        let label_len =
+            u16::try_from(entry.label.len()).map_err(|_| KeystoreError::InvalidFormat)?;
+        let secret_len = u8::try_from(secret.len()).map_err(|_| KeystoreError::InvalidFormat)?;
+        bytes.push(kind);
+        bytes.extend(label_len.to_be_bytes());
+        bytes.extend(entry.label.as_bytes());
+        bytes.extend(entry.created.to_be_bytes());
+        bytes.push(secret_len);
+        bytes.extend(secret);
+    }
+    Ok(bytes)
+}
+
+fn decode_entries(bytes: &[u8]) -> Result<Vec<Entry>, KeystoreError> {
+    let mut cursor = Cursor { bytes };
+    let count = cursor.u32()?;
+    let mut entries = vec![];
+    for _ in 0..count {
+        let kind = cursor.u8()?;
+        let label_len = cursor.u16()? as usize;
+        let label = String::from_utf8(cursor.take(label_len)?.to_vec())
+            .map_err(|_| KeystoreError::InvalidFormat)?;
+        let created = cursor.u64()?;
+        let secret_len = cursor.u8()? as usize;
+        let secret = cursor.take(secret_len)?;
+        let secret = match kind {
+            KIND_KEY => {
+                let bytes: [u8; 32] = secret
+                    .try_into()
+                    .map_err(|_| KeystoreError::InvalidFormat)?;
+                Secret::Key(
+                    PrivateKey::from_bytes(bytes).map_err(|_| KeystoreError::InvalidFormat)?,
+                )
+            }
+            KIND_SEED => Secret::Seed(secret.to_vec()),
+            _ => return Err(KeystoreError::InvalidFormat),
+        };
+        entries.push(Entry {
+            label,
+            created,
+            secret,
+        });
+    }
+    if !cursor.bytes.is_empty() {
+        return Err(KeystoreError::InvalidFormat);
+    }
+    Ok(entries)
+}
+
+impl Keystore {
+    pub fn new(password: &str) -> Result<Self, KeystoreError> {
+        Self::with_params(password, KdfParams::default())
+    }
+
+    pub fn with_params(password: &str, params: KdfParams) -> Result<Self, KeystoreError> {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let key = derive_key(password, &salt, params)?;
+        Ok(Self {
+            entries: vec![],
+            params,
+            salt,
+            key,
+        })
+    }
+
+    pub fn unlock(bytes: &[u8], password: &str) -> Result<Self, KeystoreError> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(KeystoreError::InvalidFormat);
+        }
+        if bytes[4] != VERSION {
+            return Err(KeystoreError::UnsupportedVersion(bytes[4]));
+        }
+        let (header, ciphertext) = bytes.split_at(HEADER_LEN);
+        let params = KdfParams {
+            log_n: header[5],
+            r: u32::from_be_bytes(header[6..10].try_into().unwrap()),
+            p: u32::from_be_bytes(header[10..14].try_into().unwrap()),
+        };
+        let salt: [u8; 16] = header[14..30].try_into().unwrap();
+        let nonce = &header[30..42];
+
+        let key = derive_key(password, &salt, params)?;
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| KeystoreError::DecryptionFailed)?;
+        Ok(Self {
+            entries: decode_entries(&plaintext)?,
+            params,
+            salt,
+            key,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, password: &str) -> Result<Self, KeystoreError> {
+        Self::unlock(&fs::read(path)?, password)
+    }
+
+    // encrypted with a fresh nonce every time
+    pub fn to_bytes(&self) -> Result<Vec<u8>, KeystoreError> {
+        let plaintext = encode_entries(&self.entries)?;
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(self.params.log_n);
+        bytes.extend(self.params.r.to_be_bytes());
+        bytes.extend(self.params.p.to_be_bytes());
+        bytes.extend(self.salt);
+        bytes.extend(nonce);
+
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &bytes,
+                },
+            )
+            .unwrap();
+        bytes.extend(ciphertext);
+        Ok(bytes)
+    }
+
+    // Written to "<path>.tmp", readable only by the owner, and then renamed over path, so an
+    // interrupted save leaves the previous file intact.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), KeystoreError> {
+        let path = path.as_ref();
+        let bytes = self.to_bytes()?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        // a leftover from an earlier interrupted save may have other permissions
+        let _ = fs::remove_file(&tmp);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let result = options
+            .open(&tmp)
+            .and_then(|mut file| {
+                file.write_all(&bytes)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        Ok(result?)
+    }
+
+    pub fn list(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn get(&self, label: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.label == label)
+    }
+
+    pub fn add_key(&mut self, label: &str, key: PrivateKey) -> Result<(), KeystoreError> {
+        // the range PrivateKey::from_bytes accepts when the entries are decoded
+        if key.secret.sign() != Sign::Plus || key.secret >= order() {
+            return Err(KeystoreError::InvalidKey);
+        }
+        self.add(label, Secret::Key(key))
+    }
+
+    // seeds are at most 255 bytes (BIP32 seeds are 16 to 64)
+    pub fn add_seed(&mut self, label: &str, seed: &[u8]) -> Result<(), KeystoreError> {
+        if seed.len() > u8::MAX as usize {
+            return Err(KeystoreError::InvalidFormat);
+        }
+        self.add(label, Secret::Seed(seed.to_vec()))
+    }
+
+    fn add(&mut self, label: &str, secret: Secret) -> Result<(), KeystoreError> {
+        if label.len() > u16::MAX as usize {
+            return Err(KeystoreError::InvalidFormat);
+        }
+        if self.get(label).is_some() {
+            return Err(KeystoreError::DuplicateLabel(label.to_string()));
+        }
+        self.entries.push(Entry {
+            label: label.to_string(),
+            created: now(),
+            secret,
+        });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, label: &str) -> Result<Entry, KeystoreError> {
+        match self.entries.iter().position(|entry| entry.label == label) {
+            Some(i) => Ok(self.entries.remove(i)),
+            None => Err(KeystoreError::NotFound(label.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+
+    // cheap parameters to keep the tests fast
+    const TEST_PARAMS: KdfParams = KdfParams {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+
+    fn keystore() -> Keystore {
+        let mut keystore = Keystore::with_params("correct horse", TEST_PARAMS).unwrap();
+        keystore
+            .add_key("cold", PrivateKey::new(BigInt::from(5003)))
+            .unwrap();
+        keystore.add_seed("wallet seed", &[0x42; 64]).unwrap();
+        keystore
+    }
+
+    #[test]
+    fn unlock_test() {
+        let keystore = keystore();
+        let bytes = keystore.to_bytes().unwrap();
+        let unlocked = Keystore::unlock(&bytes, "correct horse").unwrap();
+        assert_eq!(unlocked.list(), keystore.list());
+        assert_eq!(
+            unlocked.get("cold").unwrap().secret,
+            Secret::Key(PrivateKey::new(BigInt::from(5003)))
+        );
+        assert_eq!(
+            Keystore::unlock(&bytes, "battery staple").err(),
+            Some(KeystoreError::DecryptionFailed)
+        );
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut keystore = keystore();
+        assert_eq!(
+            keystore.add_key("cold", PrivateKey::new(BigInt::from(2021))),
+            Err(KeystoreError::DuplicateLabel("cold".to_string()))
+        );
+        let removed = keystore.remove("cold").unwrap();
+        assert_eq!(removed.label, "cold");
+        assert_eq!(
+            keystore.remove("cold"),
+            Err(KeystoreError::NotFound("cold".to_string()))
+        );
+
+        let unlocked = Keystore::unlock(&keystore.to_bytes().unwrap(), "correct horse").unwrap();
+        let labels: Vec<&str> = unlocked.list().iter().map(|e| e.label.as_str()).collect();
+        assert_eq!(labels, vec!["wallet seed"]);
+    }
+
+    #[test]
+    fn integrity_check() {
+        let bytes = keystore().to_bytes().unwrap();
+        // ciphertext
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().unwrap() ^= 0x01;
+        assert_eq!(
+            Keystore::unlock(&tampered, "correct horse").err(),
+            Some(KeystoreError::DecryptionFailed)
+        );
+        // header, authenticated as associated data
+        let mut tampered = bytes.clone();
+        tampered[20] ^= 0x01;
+        assert_eq!(
+            Keystore::unlock(&tampered, "correct horse").err(),
+            Some(KeystoreError::DecryptionFailed)
+        );
+
+        let mut tampered = bytes.clone();
+        tampered[4] = 2;
+        assert_eq!(
+            Keystore::unlock(&tampered, "correct horse").err(),
+            Some(KeystoreError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            Keystore::unlock(b"PBKS", "correct horse").err(),
+            Some(KeystoreError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn kdf_params_limit() {
+        let bytes = keystore().to_bytes().unwrap();
+        // log_n = 21, rejected before any work is done
+        let mut tampered = bytes.clone();
+        tampered[5] = MAX_LOG_N + 1;
+        assert_eq!(
+            Keystore::unlock(&tampered, "correct horse").err(),
+            Some(KeystoreError::InvalidFormat)
+        );
+        // r = 8, p = 3
+        let mut tampered = bytes.clone();
+        tampered[10..14].copy_from_slice(&3u32.to_be_bytes());
+        assert_eq!(
+            Keystore::unlock(&tampered, "correct horse").err(),
+            Some(KeystoreError::InvalidFormat)
+        );
+        // r * p overflows a u32
+        let mut tampered = bytes.clone();
+        tampered[6..14].copy_from_slice(&[0xff; 8]);
+        assert_eq!(
+            Keystore::unlock(&tampered, "correct horse").err(),
+            Some(KeystoreError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn invalid_key() {
+        let mut keystore = keystore();
+        for secret in [
+            BigInt::from(0),
+            order(),
+            BigInt::from(2).pow(256),
+            BigInt::from(-1),
+        ] {
+            assert_eq!(
+                keystore.add_key("bad", PrivateKey::new(secret)),
+                Err(KeystoreError::InvalidKey)
+            );
+        }
+        assert_eq!(keystore.get("bad"), None);
+        let bytes = keystore.to_bytes().unwrap();
+        let unlocked = Keystore::unlock(&bytes, "correct horse").unwrap();
+        assert_eq!(unlocked.list(), keystore.list());
+    }
+
+    #[test]
+    fn label_too_long() {
+        let entry = Entry {
+            label: "x".repeat(u16::MAX as usize + 1),
+            created: 0,
+            secret: Secret::Seed(vec![0x42; 16]),
+        };
+        assert_eq!(
+            encode_entries(std::slice::from_ref(&entry)),
+            Err(KeystoreError::InvalidFormat)
+        );
+        let entry = Entry {
+            label: "x".repeat(u16::MAX as usize),
+            ..entry
+        };
+        assert_eq!(true, encode_entries(&[entry]).is_ok());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("keystore-test-{}", std::process::id()));
+        let keystore = keystore();
+        keystore.save(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        // saving again replaces the file
+        keystore.save(&path).unwrap();
+        let loaded = Keystore::load(&path, "correct horse").unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.list(), keystore.list());
+        assert_eq!(
+            Keystore::load(&path, "correct horse").err(),
+            Some(KeystoreError::Io(io::ErrorKind::NotFound))
+        );
+    }
+}
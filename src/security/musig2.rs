@@ -0,0 +1,762 @@
+// MuSig2 multi-signatures (BIP327)
+use super::private_key::PrivateKey;
+use super::schnorr_signature::SchnorrSignature;
+use crate::ecc::s256_point::S256Point;
+use crate::util::bigint::{order, to_32_bytes};
+use crate::util::tagged_hash::{tagged_hash, Tag};
+use num_bigint::{BigInt, Sign};
+use num_traits::{One, Zero};
+use rand::RngCore;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MuSig2Error {
+    // the aggregate (or tweaked) public key is the point at infinity
+    InfinityKey,
+    InvalidTweak,
+    InvalidPubNonce,
+    InvalidAggNonce,
+    InvalidSecNonce,
+    InvalidSecretKey,
+    // the secret key does not belong to the public key in the secnonce
+    KeyMismatch,
+    // the signer's public key is not one of the session's public keys
+    UnknownSigner,
+    // holds the index of the partial signature that is out of range
+    InvalidPartialSig(usize),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct KeyAggContext {
+    pub q: S256Point,
+    gacc: BigInt,
+    tacc: BigInt,
+}
+
+// neither Clone nor PartialEq, so a secnonce can't be copied and signed with twice
+#[derive(Debug)]
+pub struct SecNonce {
+    k1: BigInt,
+    k2: BigInt,
+    pk: S256Point,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PubNonce {
+    pub r1: S256Point,
+    pub r2: S256Point,
+}
+
+// unlike PubNonce, each point of AggNonce may be the point at infinity
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AggNonce {
+    pub r1: S256Point,
+    pub r2: S256Point,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SessionContext {
+    pub aggnonce: AggNonce,
+    pub pubkeys: Vec<S256Point>,
+    // (tweak, is_xonly) pairs applied in order to the aggregate key
+    pub tweaks: Vec<(BigInt, bool)>,
+    pub msg: Vec<u8>,
+}
+
+struct SessionValues {
+    q: S256Point,
+    gacc: BigInt,
+    tacc: BigInt,
+    b: BigInt,
+    r: S256Point,
+    e: BigInt,
+}
+
+fn int(bytes: &[u8]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, bytes)
+}
+
+// 33 byte compressed point -> S256Point
+fn cpoint(bytes: &[u8]) -> Option<S256Point> {
+    if bytes.len() != 33 || (bytes[0] != 0x02 && bytes[0] != 0x03) {
+        return None;
+    }
+    let p = S256Point::lift_x(int(&bytes[1..]))?;
+    if bytes[0] == 0x02 {
+        Some(p)
+    } else {
+        Some(-p)
+    }
+}
+
+// same as cpoint, but 33 zero bytes stand for the point at infinity
+fn cpoint_ext(bytes: &[u8]) -> Option<S256Point> {
+    if bytes.len() == 33 && bytes.iter().all(|b| *b == 0x0) {
+        Some(S256Point::new(None, None))
+    } else {
+        cpoint(bytes)
+    }
+}
+
+fn cbytes_ext(p: &S256Point) -> Vec<u8> {
+    match p.x {
+        Some(_) => p.clone().sec(true),
+        None => vec![0x0; 33],
+    }
+}
+
+pub fn key_sort(pubkeys: &[S256Point]) -> Vec<S256Point> {
+    let mut sorted = pubkeys.to_vec();
+    sorted.sort_by_key(|pk| pk.clone().sec(true));
+    sorted
+}
+
+fn hash_keys(pubkeys: &[S256Point]) -> Vec<u8> {
+    let bytes: Vec<u8> = pubkeys.iter().flat_map(|pk| pk.clone().sec(true)).collect();
+    tagged_hash("KeyAgg list", &bytes)
+}
+
+fn key_agg_coeff(pubkeys: &[S256Point], pk: &S256Point) -> BigInt {
+    // the first key different from pubkeys[0] gets coefficient 1
+    if pubkeys.iter().find(|p| *p != &pubkeys[0]) == Some(pk) {
+        return BigInt::one();
+    }
+    let mut bytes = hash_keys(pubkeys);
+    bytes.extend(pk.clone().sec(true));
+    int(&tagged_hash("KeyAgg coefficient", &bytes)) % order()
+}
+
+pub fn key_agg(pubkeys: &[S256Point]) -> Result<KeyAggContext, MuSig2Error> {
+    let q = pubkeys.iter().fold(S256Point::new(None, None), |acc, pk| {
+        acc + pk.clone().rmul(key_agg_coeff(pubkeys, pk))
+    });
+    if q.x.is_none() {
+        return Err(MuSig2Error::InfinityKey);
+    }
+    Ok(KeyAggContext {
+        q,
+        gacc: BigInt::one(),
+        tacc: BigInt::zero(),
+    })
+}
+
+impl KeyAggContext {
+    pub fn apply_tweak(self, tweak: &BigInt, is_xonly: bool) -> Result<Self, MuSig2Error> {
+        let n = order();
+        if tweak >= &n {
+            return Err(MuSig2Error::InvalidTweak);
+        }
+        let negate = is_xonly && !self.q.has_even_y();
+        let (q, g) = if negate {
+            (-&self.q, &n - BigInt::one())
+        } else {
+            (self.q, BigInt::one())
+        };
+        let q = q + S256Point::new_g().rmul(tweak.clone());
+        if q.x.is_none() {
+            return Err(MuSig2Error::InfinityKey);
+        }
+        Ok(Self {
+            q,
+            gacc: &g * self.gacc % &n,
+            tacc: (tweak + &g * self.tacc) % &n,
+        })
+    }
+
+    pub fn xonly_pubkey(&self) -> Vec<u8> {
+        self.q.xonly()
+    }
+}
+
+impl SecNonce {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = to_32_bytes(&self.k1);
+        result.extend(to_32_bytes(&self.k2));
+        result.extend(self.pk.clone().sec(true));
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, MuSig2Error> {
+        if bytes.len() != 97 {
+            return Err(MuSig2Error::InvalidSecNonce);
+        }
+        let pk = cpoint(&bytes[64..]).ok_or(MuSig2Error::InvalidSecNonce)?;
+        Ok(Self {
+            k1: int(&bytes[..32]),
+            k2: int(&bytes[32..64]),
+            pk,
+        })
+    }
+}
+
+impl PubNonce {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.r1.clone().sec(true);
+        result.extend(self.r2.clone().sec(true));
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, MuSig2Error> {
+        if bytes.len() != 66 {
+            return Err(MuSig2Error::InvalidPubNonce);
+        }
+        match (cpoint(&bytes[..33]), cpoint(&bytes[33..])) {
+            (Some(r1), Some(r2)) => Ok(Self { r1, r2 }),
+            _ => Err(MuSig2Error::InvalidPubNonce),
+        }
+    }
+}
+
+impl AggNonce {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = cbytes_ext(&self.r1);
+        result.extend(cbytes_ext(&self.r2));
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, MuSig2Error> {
+        if bytes.len() != 66 {
+            return Err(MuSig2Error::InvalidAggNonce);
+        }
+        match (cpoint_ext(&bytes[..33]), cpoint_ext(&bytes[33..])) {
+            (Some(r1), Some(r2)) => Ok(Self { r1, r2 }),
+            _ => Err(MuSig2Error::InvalidAggNonce),
+        }
+    }
+}
+
+pub fn nonce_gen(
+    sk: Option<&PrivateKey>,
+    pk: &S256Point,
+    aggpk: Option<&[u8]>,
+    msg: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> (SecNonce, PubNonce) {
+    let mut rand = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut rand);
+    nonce_gen_with_rand(&rand, sk, pk, aggpk, msg, extra_in)
+}
+
+// rand must be fresh randomness for every call, it is only a parameter for testing
+pub fn nonce_gen_with_rand(
+    rand: &[u8; 32],
+    sk: Option<&PrivateKey>,
+    pk: &S256Point,
+    aggpk: Option<&[u8]>,
+    msg: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> (SecNonce, PubNonce) {
+    let rand = match sk {
+        Some(sk) => to_32_bytes(&sk.secret)
+            .iter()
+            .zip(tagged_hash("MuSig/aux", rand))
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<u8>>(),
+        None => rand.to_vec(),
+    };
+    let pk_bytes = pk.clone().sec(true);
+    let aggpk = aggpk.unwrap_or(&[]);
+    let msg_prefixed = match msg {
+        Some(msg) => {
+            let mut bytes = vec![0x01];
+            bytes.extend((msg.len() as u64).to_be_bytes());
+            bytes.extend(msg);
+            bytes
+        }
+        None => vec![0x00],
+    };
+    let extra_in = extra_in.unwrap_or(&[]);
+
+    let n = order();
+    let k: Vec<BigInt> = (0u8..2)
+        .map(|i| {
+            let mut bytes = rand.clone();
+            bytes.push(pk_bytes.len() as u8);
+            bytes.extend(&pk_bytes);
+            bytes.push(aggpk.len() as u8);
+            bytes.extend(aggpk);
+            bytes.extend(&msg_prefixed);
+            bytes.extend((extra_in.len() as u32).to_be_bytes());
+            bytes.extend(extra_in);
+            bytes.push(i);
+            int(&tagged_hash("MuSig/nonce", &bytes)) % &n
+        })
+        .collect();
+    if k[0].is_zero() || k[1].is_zero() {
+        panic!("nonce_gen produced a zero nonce");
+    }
+
+    let g = S256Point::new_g();
+    let pubnonce = PubNonce {
+        r1: g.clone().rmul(k[0].clone()),
+        r2: g.rmul(k[1].clone()),
+    };
+    let secnonce = SecNonce {
+        k1: k[0].clone(),
+        k2: k[1].clone(),
+        pk: pk.clone(),
+    };
+    (secnonce, pubnonce)
+}
+
+pub fn nonce_agg(pubnonces: &[PubNonce]) -> AggNonce {
+    let infinity = S256Point::new(None, None);
+    AggNonce {
+        r1: pubnonces.iter().fold(infinity.clone(), |acc, p| acc + &p.r1),
+        r2: pubnonces.iter().fold(infinity, |acc, p| acc + &p.r2),
+    }
+}
+
+impl SessionContext {
+    pub fn new(
+        aggnonce: AggNonce,
+        pubkeys: Vec<S256Point>,
+        tweaks: Vec<(BigInt, bool)>,
+        msg: Vec<u8>,
+    ) -> Self {
+        Self {
+            aggnonce,
+            pubkeys,
+            tweaks,
+            msg,
+        }
+    }
+
+    fn values(&self) -> Result<SessionValues, MuSig2Error> {
+        let mut ctx = key_agg(&self.pubkeys)?;
+        for (tweak, is_xonly) in &self.tweaks {
+            ctx = ctx.apply_tweak(tweak, *is_xonly)?;
+        }
+        let n = order();
+
+        let mut bytes = self.aggnonce.serialize();
+        bytes.extend(ctx.q.xonly());
+        bytes.extend(&self.msg);
+        let b = int(&tagged_hash("MuSig/noncecoef", &bytes)) % &n;
+
+        let r = &self.aggnonce.r1 + self.aggnonce.r2.clone().rmul(b.clone());
+        let r = if r.x.is_none() { S256Point::new_g() } else { r };
+
+        let mut bytes = r.xonly();
+        bytes.extend(ctx.q.xonly());
+        bytes.extend(&self.msg);
+        let e = int(&Tag::Bip340Challenge.hash(&bytes)) % &n;
+
+        Ok(SessionValues {
+            q: ctx.q,
+            gacc: ctx.gacc,
+            tacc: ctx.tacc,
+            b,
+            r,
+            e,
+        })
+    }
+
+    fn key_agg_coeff(&self, pk: &S256Point) -> Result<BigInt, MuSig2Error> {
+        if !self.pubkeys.contains(pk) {
+            return Err(MuSig2Error::UnknownSigner);
+        }
+        Ok(key_agg_coeff(&self.pubkeys, pk))
+    }
+}
+
+// secnonce is consumed so that it can't be reused for a second signature
+pub fn sign(
+    secnonce: SecNonce,
+    sk: &PrivateKey,
+    session: &SessionContext,
+) -> Result<BigInt, MuSig2Error> {
+    let v = session.values()?;
+    let n = order();
+    let in_range = |k: &BigInt| !k.is_zero() && k < &n;
+
+    if !in_range(&secnonce.k1) || !in_range(&secnonce.k2) {
+        return Err(MuSig2Error::InvalidSecNonce);
+    }
+    let (k1, k2) = if v.r.has_even_y() {
+        (secnonce.k1, secnonce.k2)
+    } else {
+        (&n - secnonce.k1, &n - secnonce.k2)
+    };
+
+    if !in_range(&sk.secret) {
+        return Err(MuSig2Error::InvalidSecretKey);
+    }
+    if sk.point != secnonce.pk {
+        return Err(MuSig2Error::KeyMismatch);
+    }
+    let a = session.key_agg_coeff(&sk.point)?;
+    let g = if v.q.has_even_y() {
+        BigInt::one()
+    } else {
+        &n - BigInt::one()
+    };
+    let d = g * v.gacc * &sk.secret % &n;
+
+    Ok((k1 + v.b * k2 + v.e * a * d) % &n)
+}
+
+pub fn partial_sig_verify(
+    psig: &BigInt,
+    pubnonce: &PubNonce,
+    pk: &S256Point,
+    session: &SessionContext,
+) -> bool {
+    let v = match session.values() {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    let a = match session.key_agg_coeff(pk) {
+        Ok(a) => a,
+        Err(_) => return false,
+    };
+    let n = order();
+    if psig >= &n {
+        return false;
+    }
+
+    let re = &pubnonce.r1 + pubnonce.r2.clone().rmul(v.b);
+    let re = if v.r.has_even_y() { re } else { -re };
+    let g = if v.q.has_even_y() {
+        BigInt::one()
+    } else {
+        &n - BigInt::one()
+    };
+    let g = g * v.gacc % &n;
+
+    S256Point::new_g().rmul(psig.clone()) == re + pk.clone().rmul(v.e * a * g % &n)
+}
+
+pub fn partial_sig_agg(
+    psigs: &[BigInt],
+    session: &SessionContext,
+) -> Result<SchnorrSignature, MuSig2Error> {
+    let v = session.values()?;
+    let n = order();
+    let mut s = BigInt::zero();
+    for (i, psig) in psigs.iter().enumerate() {
+        if psig >= &n {
+            return Err(MuSig2Error::InvalidPartialSig(i));
+        }
+        s += psig;
+    }
+    let g = if v.q.has_even_y() {
+        BigInt::one()
+    } else {
+        &n - BigInt::one()
+    };
+    let s = (s + v.e * g * v.tacc) % &n;
+    Ok(SchnorrSignature::new(v.r.x.unwrap().num, s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex::{hex, FromHex};
+
+    fn point(s: &str) -> S256Point {
+        S256Point::new(None, None).parse(Vec::from_hex(s).unwrap())
+    }
+
+    fn scalar(s: &str) -> BigInt {
+        int(&Vec::from_hex(s).unwrap())
+    }
+
+    // public keys of the BIP327 key_agg vectors
+    fn key_agg_pubkeys() -> Vec<S256Point> {
+        vec![
+            point("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            point("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            point("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66"),
+        ]
+    }
+
+    // public keys, nonces and message of the BIP327 sign_verify vectors
+    fn sign_verify_sk() -> PrivateKey {
+        PrivateKey::new(scalar(
+            "7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671",
+        ))
+    }
+
+    fn sign_verify_pubkeys() -> Vec<S256Point> {
+        vec![
+            point("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+            point("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            point("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661"),
+        ]
+    }
+
+    fn sign_verify_secnonce() -> SecNonce {
+        SecNonce::parse(&Vec::from_hex("508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F703935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9").unwrap()).unwrap()
+    }
+
+    fn sign_verify_session(pubkeys: Vec<S256Point>) -> SessionContext {
+        let aggnonce = AggNonce::parse(&Vec::from_hex("028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9").unwrap()).unwrap();
+        let msg = Vec::from_hex("F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF")
+            .unwrap();
+        SessionContext::new(aggnonce, pubkeys, vec![], msg)
+    }
+
+    #[test]
+    fn key_agg_test1() {
+        let ctx = key_agg(&key_agg_pubkeys()).unwrap();
+        assert_eq!(
+            hex(&ctx.xonly_pubkey()),
+            "90539eede565f5d054f32cc0c220126889ed1e5d193baf15aef344fe59d4610c"
+        );
+    }
+
+    #[test]
+    fn key_agg_test2() {
+        let mut pubkeys = key_agg_pubkeys();
+        pubkeys.reverse();
+        let ctx = key_agg(&pubkeys).unwrap();
+        assert_eq!(
+            hex(&ctx.xonly_pubkey()),
+            "6204de8b083426dc6eaf9502d27024d53fc826bf7d2012148a0575435df54b2b"
+        );
+    }
+
+    #[test]
+    fn key_agg_test3() {
+        let pk = key_agg_pubkeys()[0].clone();
+        let ctx = key_agg(&[pk.clone(), pk.clone(), pk]).unwrap();
+        assert_eq!(
+            hex(&ctx.xonly_pubkey()),
+            "b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935"
+        );
+    }
+
+    #[test]
+    fn key_sort_test() {
+        let pubkeys = key_agg_pubkeys();
+        let sorted = key_sort(&pubkeys);
+        assert_eq!(
+            sorted,
+            vec![pubkeys[2].clone(), pubkeys[0].clone(), pubkeys[1].clone()]
+        );
+    }
+
+    #[test]
+    fn apply_tweak_out_of_range() {
+        let ctx = KeyAggContext {
+            q: key_agg_pubkeys()[0].clone(),
+            gacc: BigInt::one(),
+            tacc: BigInt::zero(),
+        };
+        assert_eq!(
+            ctx.apply_tweak(&order(), true),
+            Err(MuSig2Error::InvalidTweak)
+        );
+    }
+
+    #[test]
+    fn apply_tweak_infinity() {
+        let pk = point("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9");
+        let ctx = key_agg(&[pk]).unwrap();
+        let tweak = scalar("252E4BD67410A76CDF933D30EAA1608214037F1B105A013ECCD3C5C184A6110B");
+        assert_eq!(
+            ctx.apply_tweak(&tweak, false),
+            Err(MuSig2Error::InfinityKey)
+        );
+    }
+
+    #[test]
+    fn nonce_gen_test1() {
+        let sk = PrivateKey::new(scalar(
+            "0202020202020202020202020202020202020202020202020202020202020202",
+        ));
+        let aggpk =
+            Vec::from_hex("0707070707070707070707070707070707070707070707070707070707070707")
+                .unwrap();
+        let msg = Vec::from_hex("0101010101010101010101010101010101010101010101010101010101010101")
+            .unwrap();
+        let extra_in =
+            Vec::from_hex("0808080808080808080808080808080808080808080808080808080808080808")
+                .unwrap();
+        let (secnonce, pubnonce) = nonce_gen_with_rand(
+            &[0x0f; 32],
+            Some(&sk),
+            &sk.point,
+            Some(&aggpk),
+            Some(&msg),
+            Some(&extra_in),
+        );
+        assert_eq!(hex(&secnonce.serialize()), "b114e502beaa4e301dd08a50264172c84e41650e6cb726b410c0694d59effb6495b5caf28d045b973d63e3c99a44b807bde375fd6cb39e46dc4a511708d0e9d2024d4b6cd1361032ca9bd2aeb9d900aa4d45d9ead80ac9423374c451a7254d0766");
+        assert_eq!(hex(&pubnonce.serialize()), "02f7be7089e8376eb355272368766b17e88e7db72047d05e56aa881ea52b3b35df02c29c8046fdd0ded4c7e55869137200fbdbfe2eb654267b6d7013602caed3115a");
+    }
+
+    #[test]
+    fn nonce_gen_test2() {
+        let pk = point("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9");
+        let (secnonce, pubnonce) = nonce_gen_with_rand(&[0x0f; 32], None, &pk, None, None, None);
+        assert_eq!(hex(&secnonce.serialize()), "89bdd787d0284e5e4d5fc572e49e316bab7e21e3b1830de37dfe80156fa41a6d0b17ae8d024c53679699a6fd7944d9c4a366b514baf43088e0708b1023dd289702f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9");
+        assert_eq!(hex(&pubnonce.serialize()), "02c96e7cb1e8aa5dac64d872947914198f607d90ecde5200de52978ad5ded63c000299ec5117c2d29edee8a2092587c3909be694d5cff0667d6c02ea4059f7cd9786");
+    }
+
+    #[test]
+    fn nonce_agg_test1() {
+        let pubnonces = vec![
+            PubNonce::parse(&Vec::from_hex("020151C80F435648DF67A22B749CD798CE54E0321D034B92B709B567D60A42E66603BA47FBC1834437B3212E89A84D8425E7BF12E0245D98262268EBDCB385D50641").unwrap()).unwrap(),
+            PubNonce::parse(&Vec::from_hex("03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60248C264CDD57D3C24D79990B0F865674EB62A0F9018277A95011B41BFC193B833").unwrap()).unwrap(),
+        ];
+        assert_eq!(hex(&nonce_agg(&pubnonces).serialize()), "035fe1873b4f2967f52fea4a06ad5a8eccbe9d0fd73068012c894e2e87ccb5804b024725377345bde0e9c33af3c43c0a29a9249f2f2956fa8cfeb55c8573d0262dc8");
+    }
+
+    #[test]
+    fn nonce_agg_test2() {
+        // the second points cancel out, so the second aggregate point is infinity
+        let pubnonces = vec![
+            PubNonce::parse(&Vec::from_hex("020151C80F435648DF67A22B749CD798CE54E0321D034B92B709B567D60A42E6660279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798").unwrap()).unwrap(),
+            PubNonce::parse(&Vec::from_hex("03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60379BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798").unwrap()).unwrap(),
+        ];
+        assert_eq!(hex(&nonce_agg(&pubnonces).serialize()), "035fe1873b4f2967f52fea4a06ad5a8eccbe9d0fd73068012c894e2e87ccb5804b000000000000000000000000000000000000000000000000000000000000000000");
+    }
+
+    #[test]
+    fn pubnonce_parse_invalid() {
+        // invalid prefix, x not on the curve and x exceeding the field size
+        let invalid = vec![
+            "04FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60248C264CDD57D3C24D79990B0F865674EB62A0F9018277A95011B41BFC193B833",
+            "03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60248C264CDD57D3C24D79990B0F865674EB62A0F9018277A95011B41BFC193B831",
+            "03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A602FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC30",
+        ];
+        for s in invalid {
+            assert_eq!(
+                PubNonce::parse(&Vec::from_hex(s).unwrap()),
+                Err(MuSig2Error::InvalidPubNonce)
+            );
+        }
+    }
+
+    #[test]
+    fn sign_test1() {
+        let session = sign_verify_session(sign_verify_pubkeys());
+        let psig = sign(sign_verify_secnonce(), &sign_verify_sk(), &session).unwrap();
+        assert_eq!(
+            hex(&to_32_bytes(&psig)),
+            "012abbcb52b3016ac03ad82395a1a415c48b93def78718e62a7a90052fe224fb"
+        );
+    }
+
+    #[test]
+    fn sign_test2() {
+        // both aggregate nonce points are infinity, so R falls back to G
+        let pubkeys = sign_verify_pubkeys()[..2].to_vec();
+        let aggnonce = AggNonce::parse(&Vec::from_hex("000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000").unwrap()).unwrap();
+        let msg = Vec::from_hex("F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF")
+            .unwrap();
+        let session = SessionContext::new(aggnonce, pubkeys, vec![], msg);
+        let psig = sign(sign_verify_secnonce(), &sign_verify_sk(), &session).unwrap();
+        assert_eq!(
+            hex(&to_32_bytes(&psig)),
+            "ae386064b26105404798f75de2eb9af5eda5387b064b83d049cb7c5e08879531"
+        );
+    }
+
+    #[test]
+    fn sign_unknown_signer() {
+        let session = sign_verify_session(sign_verify_pubkeys()[1..].to_vec());
+        assert_eq!(
+            sign(sign_verify_secnonce(), &sign_verify_sk(), &session),
+            Err(MuSig2Error::UnknownSigner)
+        );
+    }
+
+    #[test]
+    fn sign_invalid_secnonce() {
+        let session = sign_verify_session(sign_verify_pubkeys());
+        let secnonce = SecNonce {
+            k1: BigInt::zero(),
+            k2: BigInt::zero(),
+            pk: sign_verify_sk().point,
+        };
+        assert_eq!(
+            sign(secnonce, &sign_verify_sk(), &session),
+            Err(MuSig2Error::InvalidSecNonce)
+        );
+
+        let bytes = sign_verify_secnonce().serialize();
+        assert_eq!(
+            SecNonce::parse(&bytes[..96]).unwrap_err(),
+            MuSig2Error::InvalidSecNonce
+        );
+        let mut bad_pk = bytes.clone();
+        bad_pk[64] = 0x04;
+        assert_eq!(
+            SecNonce::parse(&bad_pk).unwrap_err(),
+            MuSig2Error::InvalidSecNonce
+        );
+    }
+
+    #[test]
+    fn sign_with_tweak() {
+        // BIP327 tweak vector: a single x-only tweak, signer at index 2
+        let pubkeys = vec![
+            point("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            point("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            point("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+        ];
+        let mut session = sign_verify_session(pubkeys);
+        let tweak = scalar("E8F791FF9225A2AF0102AFFF4A9A723D9612A682A25EBE79802B263CDFCD83BB");
+        session.tweaks.push((tweak, true));
+        let psig = sign(sign_verify_secnonce(), &sign_verify_sk(), &session).unwrap();
+        assert_eq!(
+            hex(&to_32_bytes(&psig)),
+            "e28a5c66e61e178c2ba19db77b6cf9f7e2f0f56c17918cd13135e60cc848fe91"
+        );
+    }
+
+    #[test]
+    fn partial_sig_verify_test() {
+        let session = sign_verify_session(sign_verify_pubkeys());
+        let pubnonce = PubNonce::parse(&Vec::from_hex("0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480").unwrap()).unwrap();
+        let pk = sign_verify_pubkeys()[0].clone();
+        let valid = scalar("012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB");
+        let wrong = scalar("FED54434AD4CFE953FC527DC6A5E5BE8F6234907B7C187559557CE87A0541C46");
+        assert_eq!(
+            true,
+            partial_sig_verify(&valid, &pubnonce, &pk, &session)
+        );
+        assert_eq!(
+            false,
+            partial_sig_verify(&wrong, &pubnonce, &pk, &session)
+        );
+        assert_eq!(
+            false,
+            partial_sig_verify(&order(), &pubnonce, &pk, &session)
+        );
+    }
+
+    #[test]
+    fn partial_sig_agg_test() {
+        let pubkeys = vec![
+            point("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+            point("02D2DC6F5DF7C56ACF38C7FA0AE7A759AE30E19B37359DFDE015872324C7EF6E05"),
+        ];
+        let aggnonce = AggNonce::parse(&Vec::from_hex("0341432722C5CD0268D829C702CF0D1CBCE57033EED201FD335191385227C3210C03D377F2D258B64AADC0E16F26462323D701D286046A2EA93365656AFD9875982B").unwrap()).unwrap();
+        let msg = Vec::from_hex("599C67EA410D005B9DA90817CF03ED3B1C868E4DA4EDF00A5880B0082C237869")
+            .unwrap();
+        let session = SessionContext::new(aggnonce, pubkeys.clone(), vec![], msg.clone());
+        let psigs = vec![
+            scalar("B15D2CD3C3D22B04DAE438CE653F6B4ECF042F42CFDED7C41B64AAF9B4AF53FB"),
+            scalar("6193D6AC61B354E9105BBDC8937A3454A6D705B6D57322A5A472A02CE99FCB64"),
+        ];
+        let sig = partial_sig_agg(&psigs, &session).unwrap();
+        assert_eq!(hex(&sig.serialize()), "041da22223ce65c92c9a0d6c2cac828aaf1eee56304fec371ddf91ebb2b9ef0912f1038025857fedeb3ff696f8b99fa4bb2c5812f6095a2e0004ec99ce18de1e");
+        let q = key_agg(&pubkeys).unwrap().q;
+        assert_eq!(true, q.verify_schnorr(&msg, sig));
+    }
+
+    #[test]
+    fn partial_sig_agg_invalid() {
+        let session = sign_verify_session(sign_verify_pubkeys());
+        let psigs = vec![
+            scalar("012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB"),
+            order(),
+        ];
+        assert_eq!(
+            partial_sig_agg(&psigs, &session),
+            Err(MuSig2Error::InvalidPartialSig(1))
+        );
+    }
+}
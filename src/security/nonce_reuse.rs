@@ -0,0 +1,194 @@
+// Forensics for ECDSA nonce reuse. Two signatures sharing r were made with the same nonce k
+// (up to sign), which exposes k and, with it, the private key of every signature that used it:
+//   s1 - s2 = (z1 - z2) / k  =>  k = (z1 - z2) / (s1 - s2),  d = (s * k - z) / r
+use super::private_key::PrivateKey;
+use super::signature::Signature;
+use crate::ecc::s256_point::S256Point;
+use crate::util::bigint::{inverse, order};
+use num_bigint::BigInt;
+use num_traits::Zero;
+use std::collections::HashMap;
+
+// (pubkey, z, signature)
+pub type SignatureRecord = (S256Point, BigInt, Signature);
+
+// the indices of the records that share r
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NonceReuse {
+    pub r: BigInt,
+    pub indices: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RecoveredKey {
+    // either k or n - k; both give the same r
+    pub nonce: BigInt,
+    pub key: PrivateKey,
+}
+
+fn modulo(a: BigInt) -> BigInt {
+    let n = order();
+    ((a % &n) + &n) % &n
+}
+
+// groups of records with the same r, in order of first appearance
+pub fn find_reused_nonces(records: &[SignatureRecord]) -> Vec<NonceReuse> {
+    let mut groups: HashMap<&BigInt, Vec<usize>> = HashMap::new();
+    for (i, (_, _, sig)) in records.iter().enumerate() {
+        groups.entry(&sig.r).or_default().push(i);
+    }
+    let mut result: Vec<NonceReuse> = groups
+        .into_iter()
+        .filter(|(_, indices)| indices.len() > 1)
+        .map(|(r, indices)| NonceReuse {
+            r: r.clone(),
+            indices,
+        })
+        .collect();
+    result.sort_by_key(|reuse| reuse.indices[0]);
+    result
+}
+
+// the private key of a signature whose nonce is known, checked against its public key
+pub fn recover_with_nonce(record: &SignatureRecord, nonce: &BigInt) -> Option<RecoveredKey> {
+    let (pubkey, z, sig) = record;
+    if sig.r.is_zero() {
+        return None;
+    }
+    // low-s normalization may have negated s, which is the same as negating k
+    for nonce in [modulo(nonce.clone()), modulo(-nonce)] {
+        let secret = modulo((&sig.s * &nonce - z) * inverse(&sig.r));
+        if secret.is_zero() {
+            continue;
+        }
+        let key = PrivateKey::new(secret);
+        if &key.point == pubkey {
+            return Some(RecoveredKey { nonce, key });
+        }
+    }
+    None
+}
+
+// two signatures by the same key with the same r
+pub fn recover_from_pair(a: &SignatureRecord, b: &SignatureRecord) -> Option<RecoveredKey> {
+    let ((pubkey_a, z_a, sig_a), (pubkey_b, z_b, sig_b)) = (a, b);
+    if pubkey_a != pubkey_b || sig_a.r != sig_b.r || z_a == z_b {
+        return None;
+    }
+    // either s may have been negated, so try s1 - s2 and s1 + s2
+    for ds in [&sig_a.s - &sig_b.s, &sig_a.s + &sig_b.s] {
+        let ds = modulo(ds);
+        if ds.is_zero() {
+            continue;
+        }
+        let nonce = modulo((z_a - z_b) * inverse(&ds));
+        if let Some(recovered) = recover_with_nonce(a, &nonce) {
+            return Some(recovered);
+        }
+    }
+    None
+}
+
+// Recovers every key reachable through reused nonces: a nonce found from a pair of
+// signatures by one key also exposes other keys that signed with it.
+pub fn scan(records: &[SignatureRecord]) -> Vec<RecoveredKey> {
+    let mut recovered: Vec<RecoveredKey> = vec![];
+    for reuse in find_reused_nonces(records) {
+        let group: Vec<&SignatureRecord> = reuse.indices.iter().map(|i| &records[*i]).collect();
+        let nonce = group.iter().enumerate().find_map(|(i, a)| {
+            group[i + 1..]
+                .iter()
+                .find_map(|b| recover_from_pair(a, b))
+                .map(|found| found.nonce)
+        });
+        let nonce = match nonce {
+            Some(nonce) => nonce,
+            None => continue,
+        };
+        for record in group {
+            if recovered.iter().any(|found| found.key.point == record.0) {
+                continue;
+            }
+            if let Some(found) = recover_with_nonce(record, &nonce) {
+                recovered.push(found);
+            }
+        }
+    }
+    recovered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a low-s signature with a caller-chosen nonce
+    fn sign_with_nonce(key: &PrivateKey, z: &BigInt, k: &BigInt) -> Signature {
+        let n = order();
+        let r = S256Point::new_g().rmul(k.clone()).x.unwrap().num;
+        let mut s = (z + &r * &key.secret) * inverse(k) % &n;
+        if s > &n / BigInt::from(2u8) {
+            s = &n - s;
+        }
+        Signature::new(r, s)
+    }
+
+    fn record(key: &PrivateKey, z: u32, k: &BigInt) -> SignatureRecord {
+        let z = BigInt::from(z);
+        let sig = sign_with_nonce(key, &z, k);
+        (key.point.clone(), z, sig)
+    }
+
+    #[test]
+    fn recover_from_pair_test() {
+        let prv = PrivateKey::new(BigInt::from(2021).pow(5));
+        let k = BigInt::from(1234567890u32);
+        let a = record(&prv, 1000, &k);
+        let b = record(&prv, 2000, &k);
+        assert_eq!(true, prv.point.clone().verify(a.1.clone(), a.2.clone()));
+
+        let recovered = recover_from_pair(&a, &b).unwrap();
+        assert_eq!(recovered.key, prv);
+        assert_eq!(
+            S256Point::new_g().rmul(recovered.nonce).x.unwrap().num,
+            a.2.r
+        );
+        assert_eq!(recover_from_pair(&a, &a), None);
+    }
+
+    #[test]
+    fn scan_test() {
+        let prv1 = PrivateKey::new(BigInt::from(5003));
+        let prv2 = PrivateKey::new(BigInt::from(2021).pow(5));
+        let prv3 = PrivateKey::new(BigInt::from(12345));
+        let k = BigInt::from(1234567890u32);
+        let records = vec![
+            record(&prv3, 4000, &BigInt::from(42)),
+            record(&prv1, 1000, &k),
+            record(&prv2, 3000, &k),
+            record(&prv1, 2000, &k),
+            record(&prv3, 5000, &BigInt::from(43)),
+        ];
+
+        assert_eq!(
+            find_reused_nonces(&records),
+            vec![NonceReuse {
+                r: records[1].2.r.clone(),
+                indices: vec![1, 2, 3],
+            }]
+        );
+        let keys: Vec<PrivateKey> = scan(&records).into_iter().map(|found| found.key).collect();
+        // prv2 only shares the nonce with prv1, but is exposed once prv1's nonce is known
+        assert_eq!(keys, vec![prv1, prv2]);
+    }
+
+    #[test]
+    fn scan_no_reuse() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let records = vec![
+            record(&prv, 1000, &BigInt::from(42)),
+            record(&prv, 2000, &BigInt::from(43)),
+        ];
+        assert_eq!(find_reused_nonces(&records), vec![]);
+        assert_eq!(scan(&records), vec![]);
+    }
+}
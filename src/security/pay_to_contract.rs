@@ -0,0 +1,48 @@
+// Pay-to-contract: commits to a contract by tweaking a public key,
+// P' = P + sha256(sec(P) || contract) * G, spendable with d + sha256(sec(P) || contract).
+use super::private_key::PrivateKey;
+use crate::ecc::s256_point::{S256Point, TweakError};
+use bitcoin_hashes::{sha256, Hash};
+use num_bigint::{BigInt, Sign};
+
+fn tweak(pubkey: &S256Point, contract: &[u8]) -> BigInt {
+    let mut bytes = pubkey.clone().sec(true);
+    bytes.extend(contract);
+    BigInt::from_bytes_be(Sign::Plus, &sha256::Hash::hash(&bytes))
+}
+
+pub fn commit(pubkey: &S256Point, contract: &[u8]) -> Result<S256Point, TweakError> {
+    pubkey.add_exp_tweak(&tweak(pubkey, contract))
+}
+
+// the private key for commit(&key.point, contract)
+pub fn commit_secret(key: &PrivateKey, contract: &[u8]) -> Result<PrivateKey, TweakError> {
+    key.add_tweak(&tweak(&key.point, contract))
+}
+
+// checks that committed is pubkey tweaked with contract
+pub fn verify_commitment(committed: &S256Point, pubkey: &S256Point, contract: &[u8]) -> bool {
+    match commit(pubkey, contract) {
+        Ok(point) => &point == committed,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_test() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let contract = b"pay 1 BTC for 100 widgets";
+        let committed = commit(&prv.point, contract).unwrap();
+        assert_eq!(commit_secret(&prv, contract).unwrap().point, committed);
+        assert_eq!(true, verify_commitment(&committed, &prv.point, contract));
+        assert_eq!(
+            false,
+            verify_commitment(&committed, &prv.point, b"pay 1 BTC for 99 widgets")
+        );
+        assert_eq!(false, verify_commitment(&prv.point, &prv.point, contract));
+    }
+}
@@ -0,0 +1,331 @@
+// Pedersen commitments C = v * H + r * G for confidential amounts. They hide v behind the
+// blinding factor r and add up: C(v1, r1) + C(v2, r2) = C(v1 + v2, r1 + r2).
+// H is a nothing-up-my-sleeve point whose x coordinate is sha256 of the uncompressed G, the
+// same generator Elements uses, so nobody knows log_G(H) and a commitment can't be opened to
+// two different values.
+use crate::ecc::s256_point::S256Point;
+use crate::util::bigint::{order, random_scalar};
+use crate::util::tagged_hash::tagged_hash;
+use bitcoin_hashes::{sha256, Hash};
+use impl_ops::*;
+use num_bigint::{BigInt, Sign};
+use num_traits::One;
+use std::ops;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PedersenError {
+    // the value doesn't fit in the number of bits the proof covers
+    ValueOutOfRange,
+    // proofs cover 1 to 64 bits
+    InvalidBits(usize),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Commitment {
+    pub point: S256Point,
+}
+
+// Borromean ring signature proving that a commitment holds a value below 2^bits.
+// The value is split into one commitment per bit, each committing to 0 or 2^i. Every digit
+// gets a ring of two keys, {C_i, C_i - 2^i * H}, and knowing the blinding factor of C_i is
+// knowing the private key of exactly one of them. The rings are chained through the shared
+// challenge e0, so the whole proof is one signature.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RangeProof {
+    pub digits: Vec<S256Point>,
+    pub e0: BigInt,
+    // s values of both ring members, per digit
+    pub s: Vec<[BigInt; 2]>,
+}
+
+fn modulo(a: BigInt) -> BigInt {
+    let n = order();
+    ((a % &n) + &n) % &n
+}
+
+pub fn generator_h() -> S256Point {
+    let x = sha256::Hash::hash(&S256Point::new_g().sec(false));
+    // happens to be a valid x coordinate, no try-and-increment needed
+    S256Point::lift_x(BigInt::from_bytes_be(Sign::Plus, &x)).unwrap()
+}
+
+// infinity has no SEC encoding, but a malformed proof may contain it
+fn encode(point: &S256Point) -> Vec<u8> {
+    match point.x {
+        Some(_) => point.clone().sec(true),
+        None => vec![0; 33],
+    }
+}
+
+impl Commitment {
+    // value and blinding are reduced mod n, so negative values commit to n - |v|
+    pub fn new(value: &BigInt, blinding: &BigInt) -> Self {
+        let point = generator_h().rmul(modulo(value.clone()))
+            + S256Point::new_g().rmul(modulo(blinding.clone()));
+        Self { point }
+    }
+
+    pub fn verify_opening(&self, value: &BigInt, blinding: &BigInt) -> bool {
+        Self::new(value, blinding) == *self
+    }
+
+    pub fn sec(&self) -> Vec<u8> {
+        encode(&self.point)
+    }
+}
+
+impl_ops::impl_op_ex!(+ |a: &Commitment, b: &Commitment| -> Commitment {
+    Commitment { point: &a.point + &b.point }
+});
+
+impl_ops::impl_op_ex!(-|a: &Commitment, b: &Commitment| -> Commitment {
+    Commitment {
+        point: &a.point + &(-&b.point),
+    }
+});
+
+// s * G - e * P
+fn ring_point(s: &BigInt, e: &BigInt, key: &S256Point) -> S256Point {
+    S256Point::new_g().rmul(s.clone()) + key.clone().rmul(order() - e)
+}
+
+// challenge for the second member of ring i, following the first member's point
+fn member_challenge(message: &[u8], point: &S256Point, ring: usize) -> BigInt {
+    let mut bytes = message.to_vec();
+    bytes.extend(encode(point));
+    bytes.extend((ring as u32).to_be_bytes());
+    BigInt::from_bytes_be(Sign::Plus, &tagged_hash("Pedersen/member", &bytes)) % order()
+}
+
+// e0 closes every ring at once: it hashes the last point of each
+fn shared_challenge(message: &[u8], last_points: &[S256Point]) -> BigInt {
+    let mut bytes = message.to_vec();
+    bytes.extend(last_points.iter().flat_map(encode));
+    BigInt::from_bytes_be(Sign::Plus, &tagged_hash("Pedersen/borromean", &bytes)) % order()
+}
+
+// 2^i * H for each digit
+fn digit_generators(bits: usize) -> Vec<S256Point> {
+    let mut generators = vec![generator_h()];
+    for i in 1..bits {
+        generators.push(&generators[i - 1] + &generators[i - 1]);
+    }
+    generators
+}
+
+// the ring of digit i: [C_i, C_i - 2^i * H]
+fn ring(digit: &S256Point, generator: &S256Point) -> [S256Point; 2] {
+    [digit.clone(), digit + &(-generator)]
+}
+
+// binds the proof to the commitment and its digits
+fn proof_message(commitment: &Commitment, digits: &[S256Point]) -> Vec<u8> {
+    let mut message = commitment.sec();
+    message.extend(digits.iter().flat_map(encode));
+    message
+}
+
+impl RangeProof {
+    // proves that Commitment::new(value, blinding) holds a value in [0, 2^64)
+    // Proving and verifying each take four scalar multiplications per bit, 256 for a full
+    // proof, which takes around ten seconds each with the BigInt point arithmetic in ecc.
+    pub fn prove(value: u64, blinding: &BigInt) -> Self {
+        Self::prove_bits(value, blinding, 64).unwrap()
+    }
+
+    // proof for [0, 2^bits); smaller proofs are cheaper and reveal an upper bound
+    pub fn prove_bits(value: u64, blinding: &BigInt, bits: usize) -> Result<Self, PedersenError> {
+        if bits == 0 || bits > 64 {
+            return Err(PedersenError::InvalidBits(bits));
+        }
+        if bits < 64 && value >> bits != 0 {
+            return Err(PedersenError::ValueOutOfRange);
+        }
+        let g = S256Point::new_g();
+        let generators = digit_generators(bits);
+
+        // the digit blinding factors add up to the commitment's, so the digits add up to it
+        let mut blindings: Vec<BigInt> = (1..bits).map(|_| random_scalar()).collect();
+        blindings.push(modulo(blinding - blindings.iter().sum::<BigInt>()));
+        let known: Vec<usize> = (0..bits).map(|i| (value >> i & 1) as usize).collect();
+        let digits: Vec<S256Point> = (0..bits)
+            .map(|i| {
+                let point = g.clone().rmul(blindings[i].clone());
+                if known[i] == 1 {
+                    point + &generators[i]
+                } else {
+                    point
+                }
+            })
+            .collect();
+        let commitment = Commitment::new(&BigInt::from(value), blinding);
+        let message = proof_message(&commitment, &digits);
+
+        // walk each ring from the known member to its end
+        let nonces: Vec<BigInt> = (0..bits).map(|_| random_scalar()).collect();
+        let mut s: Vec<[BigInt; 2]> = vec![];
+        let mut last_points = vec![];
+        for i in 0..bits {
+            let keys = ring(&digits[i], &generators[i]);
+            let nonce_point = g.clone().rmul(nonces[i].clone());
+            if known[i] == 0 {
+                let e1 = member_challenge(&message, &nonce_point, i);
+                let s1 = random_scalar();
+                last_points.push(ring_point(&s1, &e1, &keys[1]));
+                s.push([BigInt::one(), s1]);
+            } else {
+                last_points.push(nonce_point);
+                s.push([random_scalar(), BigInt::one()]);
+            }
+        }
+        let e0 = shared_challenge(&message, &last_points);
+
+        // then from e0 back round to the known member, which closes the ring
+        for i in 0..bits {
+            if known[i] == 0 {
+                s[i][0] = modulo(&nonces[i] + &e0 * &blindings[i]);
+            } else {
+                let keys = ring(&digits[i], &generators[i]);
+                let e1 = member_challenge(&message, &ring_point(&s[i][0], &e0, &keys[0]), i);
+                s[i][1] = modulo(&nonces[i] + e1 * &blindings[i]);
+            }
+        }
+        Ok(Self { digits, e0, s })
+    }
+
+    pub fn bits(&self) -> usize {
+        self.digits.len()
+    }
+
+    // the committed value is below 2^bits()
+    pub fn verify(&self, commitment: &Commitment) -> bool {
+        let bits = self.bits();
+        if bits == 0 || bits > 64 || self.s.len() != bits {
+            return false;
+        }
+        let sum = self
+            .digits
+            .iter()
+            .fold(S256Point::new(None, None), |acc, digit| acc + digit);
+        if sum != commitment.point {
+            return false;
+        }
+
+        let generators = digit_generators(bits);
+        let message = proof_message(commitment, &self.digits);
+        let last_points: Vec<S256Point> = (0..bits)
+            .map(|i| {
+                let keys = ring(&self.digits[i], &generators[i]);
+                let e1 =
+                    member_challenge(&message, &ring_point(&self.s[i][0], &self.e0, &keys[0]), i);
+                ring_point(&self.s[i][1], &e1, &keys[1])
+            })
+            .collect();
+        shared_challenge(&message, &last_points) == self.e0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex::hex;
+
+    #[test]
+    fn generator_h_test() {
+        let h = generator_h();
+        // secp256k1-zkp's generator H
+        assert_eq!(
+            hex(&h.clone().sec(false)),
+            "0450929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac031d3c6863973926e049e637cb1b5f40a36dac28af1766968c30c2313f3a38904"
+        );
+        assert_eq!(false, h == S256Point::new_g());
+    }
+
+    #[test]
+    fn homomorphic_test() {
+        let (r1, r2) = (BigInt::from(1234), BigInt::from(5678));
+        let c1 = Commitment::new(&BigInt::from(3), &r1);
+        let c2 = Commitment::new(&BigInt::from(5), &r2);
+
+        let sum = &c1 + &c2;
+        assert_eq!(true, sum.verify_opening(&BigInt::from(8), &(&r1 + &r2)));
+        assert_eq!(false, sum.verify_opening(&BigInt::from(9), &(&r1 + &r2)));
+        assert_eq!(false, sum.verify_opening(&BigInt::from(8), &r1));
+
+        // inputs minus outputs commit to zero when values balance
+        let diff = &sum - &c2;
+        assert_eq!(diff, c1);
+        assert_eq!(
+            true,
+            (&c1 - &c2).verify_opening(&BigInt::from(-2), &(&r1 - &r2))
+        );
+    }
+
+    #[test]
+    fn range_proof_test() {
+        let blinding = BigInt::from(2021).pow(5);
+        let commitment = Commitment::new(&BigInt::from(11), &blinding);
+        let proof = RangeProof::prove_bits(11, &blinding, 4).unwrap();
+        assert_eq!(proof.bits(), 4);
+        assert_eq!(true, proof.verify(&commitment));
+
+        // the digits don't add up to another commitment
+        let other = Commitment::new(&BigInt::from(12), &blinding);
+        assert_eq!(false, proof.verify(&other));
+
+        let mut tampered = proof.clone();
+        tampered.s[2][0] += 1;
+        assert_eq!(false, tampered.verify(&commitment));
+
+        assert_eq!(
+            RangeProof::prove_bits(16, &blinding, 4),
+            Err(PedersenError::ValueOutOfRange)
+        );
+        assert_eq!(
+            RangeProof::prove_bits(1, &blinding, 65),
+            Err(PedersenError::InvalidBits(65))
+        );
+    }
+
+    #[test]
+    fn range_proof_64_bits() {
+        let blinding = BigInt::from(2021).pow(5);
+        // bit 63 set, which prove_bits must not shift out of range
+        let value = 1u64 << 63 | 5;
+        let commitment = Commitment::new(&BigInt::from(value), &blinding);
+        let proof = RangeProof::prove(value, &blinding);
+        assert_eq!(proof.bits(), 64);
+        assert_eq!(true, proof.verify(&commitment));
+        assert_eq!(
+            false,
+            proof.verify(&Commitment::new(&BigInt::from(value - 1), &blinding))
+        );
+        assert_eq!(
+            RangeProof::prove_bits(value, &blinding, 65),
+            Err(PedersenError::InvalidBits(65))
+        );
+    }
+
+    // a few full 64-bit proofs take too long for every run; run with --ignored
+    #[test]
+    #[ignore = "slow"]
+    fn range_proof_64_bits_extremes() {
+        let blinding = BigInt::from(2021).pow(5);
+        let commitment = Commitment::new(&BigInt::from(u64::MAX), &blinding);
+        let proof = RangeProof::prove(u64::MAX, &blinding);
+        assert_eq!(proof.bits(), 64);
+        assert_eq!(true, proof.verify(&commitment));
+        // every digit is 1, so each ring was closed from its second member
+        let mut tampered = proof.clone();
+        tampered.s[63][1] += 1;
+        assert_eq!(false, tampered.verify(&commitment));
+
+        let commitment = Commitment::new(&BigInt::from(0), &blinding);
+        let proof = RangeProof::prove(0, &blinding);
+        assert_eq!(true, proof.verify(&commitment));
+        assert_eq!(
+            false,
+            proof.verify(&Commitment::new(&BigInt::from(1), &blinding))
+        );
+    }
+}
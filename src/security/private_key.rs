@@ -1,12 +1,42 @@
+use super::public_key::PublicKey;
 use super::signature::Signature;
-use crate::ecc::s256_point::S256Point;
-use crate::util::base58::encode_base58_checksum;
-use num_bigint::{BigInt, RandBigInt};
+use crate::ecc::s256_point::{S256Point, TweakError};
+use crate::network::Network;
+use crate::util::base58::{decode_base58_check_version, encode_base58_checksum, Base58Error};
+use crate::util::bigint::to_32_bytes;
+use crate::util::hex::{FromHex, HexError};
+use bitcoin_hashes::{sha256, Hash};
+use num_bigint::{BigInt, RandBigInt, Sign};
 use num_traits::Zero;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum KeyError {
+    Base58(Base58Error),
+    InvalidLength(usize),
+    InvalidPrefix(u8),
+    InvalidCompressionFlag(u8),
+    Hex(HexError),
+    // secret is 0 or not less than the curve order n
+    OutOfRange,
+}
+
+impl From<Base58Error> for KeyError {
+    fn from(e: Base58Error) -> Self {
+        KeyError::Base58(e)
+    }
+}
+
+impl From<HexError> for KeyError {
+    fn from(e: HexError) -> Self {
+        KeyError::Hex(e)
+    }
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PrivateKey {
-    secret: BigInt,
+    pub(crate) secret: BigInt,
     pub point: S256Point,
 }
 
@@ -17,8 +47,91 @@ impl PrivateKey {
         Self { secret, point: p }
     }
 
+    // a fresh key from the operating system's CSPRNG
+    pub fn generate() -> Self {
+        Self::generate_with_rng(&mut OsRng)
+    }
+
+    // draws 32 bytes until they form a secret in [1, n-1]
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        loop {
+            let mut bytes = [0u8; 32];
+            rng.fill_bytes(&mut bytes);
+            if let Ok(key) = Self::from_bytes(bytes) {
+                return key;
+            }
+        }
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Result<Self, KeyError> {
+        let secret = BigInt::from_bytes_be(Sign::Plus, &bytes);
+        if secret.is_zero() || secret >= S256Point::new(None, None).n {
+            return Err(KeyError::OutOfRange);
+        }
+        Ok(Self::new(secret))
+    }
+
+    // counterpart of hex(): exactly 64 hex digits
+    pub fn from_hex(s: &str) -> Result<Self, KeyError> {
+        Self::from_bytes(<[u8; 32]>::from_hex(s)?)
+    }
+
+    // returns (key, compressed, network), the arguments wif() was called with; every test
+    // network comes back as Testnet3
+    pub fn from_wif(s: &str) -> Result<(Self, bool, Network), KeyError> {
+        let (prefix, payload) = decode_base58_check_version(s)?;
+
+        // 32 byte secret (+ 0x01 when compressed)
+        let compressed = match payload.len() {
+            32 => false,
+            33 => true,
+            len => return Err(KeyError::InvalidLength(len)),
+        };
+        if compressed && payload[32] != 0x01 {
+            return Err(KeyError::InvalidCompressionFlag(payload[32]));
+        }
+        let network = Network::from_wif_prefix(prefix).ok_or(KeyError::InvalidPrefix(prefix))?;
+
+        let key = Self::from_bytes(payload[..32].try_into().unwrap())?;
+        Ok((key, compressed, network))
+    }
+
+    // panics for a zero secret, whose point is the point at infinity
+    pub fn public_key(&self, compressed: bool) -> PublicKey {
+        PublicKey::new(self.point.clone(), compressed).unwrap()
+    }
+
+    // (secret + tweak) mod n
+    pub fn add_tweak(&self, tweak: &BigInt) -> Result<Self, TweakError> {
+        let n = &self.point.n;
+        if tweak.sign() == Sign::Minus || tweak >= n {
+            return Err(TweakError::InvalidTweak);
+        }
+        let secret = (&self.secret + tweak) % n;
+        if secret.is_zero() {
+            return Err(TweakError::Infinity);
+        }
+        Ok(Self::new(secret))
+    }
+
+    // (secret * tweak) mod n
+    pub fn mul_tweak(&self, tweak: &BigInt) -> Result<Self, TweakError> {
+        let n = &self.point.n;
+        if tweak.sign() != Sign::Plus || tweak >= n {
+            return Err(TweakError::InvalidTweak);
+        }
+        Ok(Self::new(&self.secret * tweak % n))
+    }
+
+    pub fn negate(&self) -> Self {
+        Self {
+            secret: &self.point.n - &self.secret,
+            point: -&self.point,
+        }
+    }
+
     pub fn hex(&self) -> String {
-        return format!("{:0>64}", self.secret.to_string());
+        return format!("{:0>64}", self.secret.to_str_radix(16));
     }
 
     pub fn sign(self, z: &BigInt) -> Signature {
@@ -35,18 +148,14 @@ impl PrivateKey {
         return Signature::new(r, s);
     }
 
-    pub fn wif(self, compressed: bool, testnet: bool) -> String {
+    pub fn wif(self, compressed: bool, network: Network) -> String {
         let (_, mut secret_byte) = self.secret.to_bytes_be();
         let diff = 32 - secret_byte.len();
         for _ in 0..diff {
             secret_byte.insert(0, 0x0);
         }
 
-        if testnet {
-            secret_byte.insert(0, 0xef);
-        } else {
-            secret_byte.insert(0, 0x80);
-        };
+        secret_byte.insert(0, network.wif_prefix());
         if compressed {
             secret_byte.push(0x01);
         }
@@ -54,6 +163,32 @@ impl PrivateKey {
         encode_base58_checksum(&secret_byte)
     }
 
+    // shared secret compatible with libsecp256k1's default: sha256 of the compressed shared point.
+    // None when the shared point is the point at infinity.
+    pub fn ecdh(&self, point: &S256Point) -> Option<[u8; 32]> {
+        self.ecdh_with_hash(point, |x, y| {
+            let mut bytes = vec![0x02 | (y[31] & 0x01)];
+            bytes.extend(x);
+            sha256::Hash::hash(&bytes).into_inner()
+        })
+    }
+
+    // hash receives the 32 byte x and y coordinates of the shared point
+    pub fn ecdh_with_hash<T, F>(&self, point: &S256Point, hash: F) -> Option<T>
+    where
+        F: FnOnce(&[u8; 32], &[u8; 32]) -> T,
+    {
+        let shared = point.clone().rmul_ct(self.secret.clone());
+        match (shared.x, shared.y) {
+            (Some(x), Some(y)) => {
+                let x: [u8; 32] = to_32_bytes(&x.num).try_into().unwrap();
+                let y: [u8; 32] = to_32_bytes(&y.num).try_into().unwrap();
+                Some(hash(&x, &y))
+            }
+            _ => None,
+        }
+    }
+
     // !TODO: I don't figure out whether this function is needed or not
     // fn deterministic_k(self, z: BigInt) {
     //     let k = BigInt::parse_bytes(b"00", 16).unwrap() * BigInt::from(32u8);
@@ -71,6 +206,7 @@ impl PrivateKey {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::hex::hex;
 
     #[test]
     fn hex_test() {
@@ -82,11 +218,104 @@ mod tests {
         )
     }
 
+    #[test]
+    fn hex_test2() {
+        let key = b"54321deadbeef";
+        let pk = PrivateKey::new(BigInt::parse_bytes(key, 16).unwrap());
+        assert_eq!(
+            pk.hex(),
+            "00000000000000000000000000000000000000000000000000054321deadbeef"
+        );
+        assert_eq!(PrivateKey::from_hex(&pk.hex()).unwrap(), pk);
+    }
+
+    #[test]
+    fn from_hex_invalid() {
+        assert_eq!(
+            PrivateKey::from_hex("08"),
+            Err(KeyError::Hex(HexError::InvalidLength(2)))
+        );
+        assert_eq!(
+            PrivateKey::from_hex(
+                "000000000000000000000000000000000000000000000000000000000000000g"
+            ),
+            Err(KeyError::Hex(HexError::InvalidCharacter('g', 63)))
+        );
+    }
+
+    #[test]
+    fn from_bytes_out_of_range() {
+        let n = S256Point::new(None, None).n;
+        let n_bytes: [u8; 32] = to_32_bytes(&n).try_into().unwrap();
+        assert_eq!(PrivateKey::from_bytes([0u8; 32]), Err(KeyError::OutOfRange));
+        assert_eq!(PrivateKey::from_bytes(n_bytes), Err(KeyError::OutOfRange));
+    }
+
+    #[test]
+    fn generate_test() {
+        let n = S256Point::new(None, None).n;
+        let prv = PrivateKey::generate();
+        assert_eq!(true, !prv.secret.is_zero() && prv.secret < n);
+        assert_ne!(prv, PrivateKey::generate());
+    }
+
+    #[test]
+    fn generate_with_rng_test() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let prv1 = PrivateKey::generate_with_rng(&mut StdRng::seed_from_u64(5003));
+        let prv2 = PrivateKey::generate_with_rng(&mut StdRng::seed_from_u64(5003));
+        assert_eq!(prv1, prv2);
+        assert_eq!(prv1.point, S256Point::new_g().rmul(prv1.secret.clone()));
+    }
+
+    #[test]
+    fn add_tweak_test() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let tweak = BigInt::from(2021).pow(5);
+        let tweaked = prv.add_tweak(&tweak).unwrap();
+        assert_eq!(tweaked.point, prv.point.add_exp_tweak(&tweak).unwrap());
+
+        let n = prv.point.n.clone();
+        assert_eq!(prv.add_tweak(&(&n - 5003)), Err(TweakError::Infinity));
+        assert_eq!(prv.add_tweak(&n), Err(TweakError::InvalidTweak));
+        assert_eq!(
+            prv.point.add_exp_tweak(&(&n - 5003)),
+            Err(TweakError::Infinity)
+        );
+    }
+
+    #[test]
+    fn mul_tweak_test() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let tweak = BigInt::from(2021);
+        let tweaked = prv.mul_tweak(&tweak).unwrap();
+        assert_eq!(tweaked, PrivateKey::new(BigInt::from(5003 * 2021)));
+        assert_eq!(tweaked.point, prv.point.mul_tweak(&tweak).unwrap());
+        assert_eq!(
+            prv.mul_tweak(&BigInt::zero()),
+            Err(TweakError::InvalidTweak)
+        );
+        assert_eq!(
+            prv.point.mul_tweak(&BigInt::zero()),
+            Err(TweakError::InvalidTweak)
+        );
+    }
+
+    #[test]
+    fn negate_test() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let negated = prv.negate();
+        assert_eq!(negated, PrivateKey::new(&prv.point.n - 5003));
+        assert_eq!(negated.negate(), prv);
+    }
+
     #[test]
     fn wif_test1() {
         let prv = PrivateKey::new(BigInt::from(5003));
         assert_eq!(
-            prv.wif(true, true),
+            prv.wif(true, Network::Testnet3),
             "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN8rFTv2sfUK"
         );
     }
@@ -94,7 +323,7 @@ mod tests {
     fn wif_test2() {
         let prv = PrivateKey::new(BigInt::from(2021).pow(5));
         assert_eq!(
-            prv.wif(false, true),
+            prv.wif(false, Network::Testnet3),
             "91avARGdfge8E4tZfYLoxeJ5sGBdNJQH4kvjpWAxgzczjbCwxic"
         );
     }
@@ -103,8 +332,107 @@ mod tests {
         let key = b"54321deadbeef";
         let prv = PrivateKey::new(BigInt::parse_bytes(key, 16).unwrap());
         assert_eq!(
-            prv.wif(true, false),
+            prv.wif(true, Network::Mainnet),
             "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S53a"
         );
     }
+
+    #[test]
+    fn from_wif_test1() {
+        let (prv, compressed, network) =
+            PrivateKey::from_wif("cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN8rFTv2sfUK").unwrap();
+        assert_eq!(prv, PrivateKey::new(BigInt::from(5003)));
+        assert_eq!((compressed, network), (true, Network::Testnet3));
+    }
+
+    #[test]
+    fn from_wif_test2() {
+        let (prv, compressed, network) =
+            PrivateKey::from_wif("91avARGdfge8E4tZfYLoxeJ5sGBdNJQH4kvjpWAxgzczjbCwxic").unwrap();
+        assert_eq!(prv, PrivateKey::new(BigInt::from(2021).pow(5)));
+        assert_eq!((compressed, network), (false, Network::Testnet3));
+    }
+
+    #[test]
+    fn from_wif_test3() {
+        let (prv, compressed, network) =
+            PrivateKey::from_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S53a").unwrap();
+        assert_eq!(
+            prv.hex(),
+            "00000000000000000000000000000000000000000000000000054321deadbeef"
+        );
+        assert_eq!((compressed, network), (true, Network::Mainnet));
+    }
+
+    #[test]
+    fn from_wif_invalid() {
+        // last character changed
+        assert_eq!(
+            PrivateKey::from_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S53b"),
+            Err(KeyError::Base58(Base58Error::InvalidChecksum))
+        );
+        assert_eq!(
+            PrivateKey::from_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S530"),
+            Err(KeyError::Base58(Base58Error::InvalidCharacter('0', 51)))
+        );
+        // P2PKH address: valid base58check, but a 20 byte hash after the 0x00 prefix
+        assert_eq!(
+            PrivateKey::from_wif("1F1Pn2y6pDb68E5nYJJeba4TLg2U7B6KF1"),
+            Err(KeyError::InvalidLength(20))
+        );
+        let mut payload = vec![0x3fu8];
+        payload.extend([0x01; 32]);
+        assert_eq!(
+            PrivateKey::from_wif(&encode_base58_checksum(&payload)),
+            Err(KeyError::InvalidPrefix(0x3f))
+        );
+        let mut payload = vec![0x80u8];
+        payload.extend([0x01; 32]);
+        payload.push(0x02);
+        assert_eq!(
+            PrivateKey::from_wif(&encode_base58_checksum(&payload)),
+            Err(KeyError::InvalidCompressionFlag(0x02))
+        );
+    }
+
+    #[test]
+    fn ecdh_test1() {
+        // expected value computed with libsecp256k1's secp256k1_ecdh
+        let prv = PrivateKey::new(BigInt::from(2021).pow(5));
+        let peer = PrivateKey::new(BigInt::from(12345));
+        assert_eq!(
+            hex(&prv.ecdh(&peer.point).unwrap()),
+            "25043acbcd52363ffa172f7e18c757e00c89164b38fa874be92dcda204a78f62"
+        );
+    }
+
+    #[test]
+    fn ecdh_test2() {
+        // expected value computed with libsecp256k1's secp256k1_ecdh
+        let prv = PrivateKey::new(BigInt::parse_bytes(b"deadbeef", 16).unwrap());
+        let peer = PrivateKey::new(BigInt::parse_bytes(b"c0ffee", 16).unwrap());
+        let shared = prv.ecdh(&peer.point).unwrap();
+        assert_eq!(
+            hex(&shared),
+            "14ffe503f9c953baca1de2c39006cf58a3997b7d4e91274dfd2a329e1de2d61d"
+        );
+        assert_eq!(peer.ecdh(&prv.point), Some(shared));
+    }
+
+    #[test]
+    fn ecdh_custom_hash() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let peer = PrivateKey::new(BigInt::from(2021));
+        let x = prv.ecdh_with_hash(&peer.point, |x, _| x.to_vec()).unwrap();
+        let shared = S256Point::new_g().rmul(BigInt::from(5003 * 2021));
+        assert_eq!(x, to_32_bytes(&shared.x.unwrap().num));
+    }
+
+    #[test]
+    fn ecdh_infinity() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let infinity = S256Point::new(None, None);
+        assert_eq!(prv.ecdh(&infinity), None);
+        assert_eq!(prv.ecdh_with_hash(&infinity, |x, _| x.to_vec()), None);
+    }
 }
@@ -0,0 +1,228 @@
+// A public key as it appears in scripts and addresses: a point plus the SEC format it is
+// serialized in. S256Point stays the type for arithmetic.
+use crate::ecc::s256_field::S256Field;
+use crate::ecc::s256_point::S256Point;
+use crate::network::Network;
+use crate::util::base58::encode_base58_checksum;
+use crate::util::hash_types::Hash160;
+use crate::util::hex::{hex, FromHex, HexError, ToHex};
+use num_bigint::{BigInt, Sign};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PublicKeyError {
+    Hex(HexError),
+    InvalidLength(usize),
+    InvalidPrefix(u8),
+    // the coordinates are not a point on secp256k1
+    NotOnCurve,
+    // the point at infinity has no SEC form
+    Infinity,
+}
+
+impl From<HexError> for PublicKeyError {
+    fn from(e: HexError) -> Self {
+        PublicKeyError::Hex(e)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PublicKey {
+    point: S256Point,
+    pub compressed: bool,
+}
+
+impl PublicKey {
+    pub fn new(point: S256Point, compressed: bool) -> Result<Self, PublicKeyError> {
+        if point.x.is_none() {
+            return Err(PublicKeyError::Infinity);
+        }
+        Ok(Self { point, compressed })
+    }
+
+    pub fn point(&self) -> &S256Point {
+        &self.point
+    }
+
+    pub fn sec(&self) -> Vec<u8> {
+        self.point.clone().sec(self.compressed)
+    }
+
+    // unlike S256Point::parse, rejects malformed input and points off the curve
+    pub fn parse(sec: &[u8]) -> Result<Self, PublicKeyError> {
+        let prefix = *sec.first().ok_or(PublicKeyError::InvalidLength(0))?;
+        match (prefix, sec.len()) {
+            (0x02 | 0x03, 33) => {
+                let point = S256Point::lift_x(BigInt::from_bytes_be(Sign::Plus, &sec[1..]))
+                    .ok_or(PublicKeyError::NotOnCurve)?;
+                let point = if prefix == 0x02 { point } else { -point };
+                Self::new(point, true)
+            }
+            (0x04, 65) => {
+                let x = BigInt::from_bytes_be(Sign::Plus, &sec[1..33]);
+                let y = BigInt::from_bytes_be(Sign::Plus, &sec[33..]);
+                let (x_field, y_field) = (S256Field::new(x.clone()), S256Field::new(y.clone()));
+                // coordinates must be below p and satisfy y^2 = x^3 + 7
+                if x_field.num != x
+                    || y_field.num != y
+                    || &y_field * &y_field
+                        != &x_field * &x_field * &x_field + S256Field::new(BigInt::from(7u8))
+                {
+                    return Err(PublicKeyError::NotOnCurve);
+                }
+                Self::new(S256Point::new(Some(x_field), Some(y_field)), false)
+            }
+            (0x02..=0x04, len) => Err(PublicKeyError::InvalidLength(len)),
+            (prefix, _) => Err(PublicKeyError::InvalidPrefix(prefix)),
+        }
+    }
+
+    pub fn hash160(&self) -> Hash160 {
+        Hash160::hash(&self.sec())
+    }
+
+    // P2PKH
+    pub fn address(&self, network: Network) -> String {
+        let mut bytes = vec![network.p2pkh_prefix()];
+        bytes.extend(self.hash160().as_bytes());
+        encode_base58_checksum(&bytes)
+    }
+}
+
+impl ToHex for PublicKey {
+    fn to_hex(&self) -> String {
+        hex(&self.sec())
+    }
+}
+
+impl FromHex for PublicKey {
+    type Error = PublicKeyError;
+
+    fn from_hex(s: &str) -> Result<Self, PublicKeyError> {
+        Self::parse(&Vec::from_hex(s)?)
+    }
+}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sec().hash(state);
+    }
+}
+
+impl PartialOrd for PublicKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PublicKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sec().cmp(&other.sec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::private_key::PrivateKey;
+    use std::collections::HashSet;
+
+    #[test]
+    fn sec_and_parse() {
+        let prv = PrivateKey::new(BigInt::from(5001u32));
+        let compressed = prv.public_key(true);
+        assert_eq!(
+            hex(&compressed.sec()),
+            "0357a4f368868a8a6d572991e484e664810ff14c05c0fa023275251151fe0e53d1"
+        );
+        assert_eq!(PublicKey::parse(&compressed.sec()).unwrap(), compressed);
+
+        let uncompressed = prv.public_key(false);
+        assert_eq!(uncompressed.sec().len(), 65);
+        assert_eq!(PublicKey::parse(&uncompressed.sec()).unwrap(), uncompressed);
+        assert_eq!(
+            PublicKey::from_hex(&uncompressed.to_hex()).unwrap(),
+            uncompressed
+        );
+        assert_eq!(
+            PublicKey::from_hex("0357a4"),
+            Err(PublicKeyError::InvalidLength(3))
+        );
+        assert_eq!(
+            PublicKey::from_hex("03z7"),
+            Err(PublicKeyError::Hex(HexError::InvalidCharacter('z', 2)))
+        );
+    }
+
+    #[test]
+    fn address_test() {
+        let prv = PrivateKey::new(BigInt::from(5002));
+        assert_eq!(
+            prv.public_key(false).address(Network::Testnet3),
+            "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA"
+        );
+        assert_eq!(
+            prv.public_key(true).hash160().as_bytes().to_vec(),
+            prv.point.clone().hash160(true)
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let mut sec = PrivateKey::new(BigInt::from(5001u32))
+            .public_key(false)
+            .sec();
+        assert_eq!(
+            PublicKey::parse(&sec[..33]),
+            Err(PublicKeyError::InvalidLength(33))
+        );
+        sec[64] ^= 0x01;
+        assert_eq!(PublicKey::parse(&sec), Err(PublicKeyError::NotOnCurve));
+        sec[0] = 0x05;
+        assert_eq!(
+            PublicKey::parse(&sec),
+            Err(PublicKeyError::InvalidPrefix(0x05))
+        );
+        assert_eq!(PublicKey::parse(&[]), Err(PublicKeyError::InvalidLength(0)));
+        assert_eq!(
+            PublicKey::new(S256Point::new(None, None), true),
+            Err(PublicKeyError::Infinity)
+        );
+        // x = 5 is not the x coordinate of any point
+        let mut sec = vec![0x02];
+        sec.extend([0; 31]);
+        sec.push(0x05);
+        assert_eq!(PublicKey::parse(&sec), Err(PublicKeyError::NotOnCurve));
+    }
+
+    #[test]
+    fn hash_and_ord() {
+        let prv1 = PrivateKey::new(BigInt::from(5001u32));
+        let prv2 = PrivateKey::new(BigInt::from(2019).pow(5));
+        let mut keys = vec![
+            prv1.public_key(true),
+            prv2.public_key(true),
+            prv1.public_key(false),
+        ];
+        keys.sort();
+        // 02... < 03... < 04...
+        assert_eq!(
+            keys,
+            vec![
+                prv2.public_key(true),
+                prv1.public_key(true),
+                prv1.public_key(false)
+            ]
+        );
+
+        let set: HashSet<PublicKey> = [
+            prv1.public_key(true),
+            prv1.public_key(true),
+            prv1.public_key(false),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(set.len(), 2);
+    }
+}
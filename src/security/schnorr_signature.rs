@@ -0,0 +1,59 @@
+use crate::util::bigint::to_32_bytes;
+use num_bigint::{BigInt, Sign};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SchnorrSignatureError {
+    // holds the length that was given instead of 64
+    InvalidLength(usize),
+}
+
+// BIP340 signature. r is the x coordinate of the nonce point R.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SchnorrSignature {
+    pub r: BigInt,
+    pub s: BigInt,
+}
+
+impl SchnorrSignature {
+    pub fn new(r: BigInt, s: BigInt) -> Self {
+        Self { r, s }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = to_32_bytes(&self.r);
+        result.extend(to_32_bytes(&self.s));
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, SchnorrSignatureError> {
+        if bytes.len() != 64 {
+            return Err(SchnorrSignatureError::InvalidLength(bytes.len()));
+        }
+        let r = BigInt::from_bytes_be(Sign::Plus, &bytes[..32]);
+        let s = BigInt::from_bytes_be(Sign::Plus, &bytes[32..]);
+        Ok(Self { r, s })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex::hex;
+
+    #[test]
+    fn serialize_test() {
+        // BIP340 test vector 4
+        let bytes_r = b"3b78ce563f89a0ed9414f5aa28ad0d96d6795f9c63";
+        let bytes_s = b"76afb1548af603b3eb45c9f8207dee1060cb71c04e80f593060b07d28308d7f4";
+        let sig = SchnorrSignature::new(
+            BigInt::parse_bytes(bytes_r, 16).unwrap(),
+            BigInt::parse_bytes(bytes_s, 16).unwrap(),
+        );
+        assert_eq!(hex(&sig.serialize()), "00000000000000000000003b78ce563f89a0ed9414f5aa28ad0d96d6795f9c6376afb1548af603b3eb45c9f8207dee1060cb71c04e80f593060b07d28308d7f4");
+        assert_eq!(SchnorrSignature::parse(&sig.serialize()), Ok(sig));
+        assert_eq!(
+            SchnorrSignature::parse(&[0u8; 63]),
+            Err(SchnorrSignatureError::InvalidLength(63))
+        );
+    }
+}
@@ -0,0 +1,161 @@
+// k-of-n Shamir secret sharing of private keys over the secp256k1 scalar field,
+// with Feldman commitments so that each holder can check their share.
+use super::private_key::PrivateKey;
+use crate::ecc::s256_point::S256Point;
+use crate::util::bigint::{inverse, order};
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::{One, Zero};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ShamirError {
+    // threshold must be in [1, shares]
+    InvalidThreshold,
+    NoShares,
+    // share indices must be unique and non-zero
+    InvalidShareIndex(u32),
+}
+
+// the point (index, value) on the secret polynomial
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Share {
+    pub index: u32,
+    pub value: BigInt,
+}
+
+// Splits the secret into `shares` shares, any `threshold` of which recover it.
+// Also returns the Feldman commitments a_j * G to the polynomial coefficients.
+pub fn split(
+    key: &PrivateKey,
+    threshold: usize,
+    shares: usize,
+) -> Result<(Vec<Share>, Vec<S256Point>), ShamirError> {
+    if threshold == 0 || threshold > shares || shares > u32::MAX as usize {
+        return Err(ShamirError::InvalidThreshold);
+    }
+    let n = order();
+    let mut rng = rand::thread_rng();
+    let mut coefficients = vec![key.secret.clone() % &n];
+    for _ in 1..threshold {
+        coefficients.push(rng.gen_bigint_range(&BigInt::zero(), &n));
+    }
+
+    let result = (1..=shares as u32)
+        .map(|index| Share {
+            index,
+            value: evaluate(&coefficients, &BigInt::from(index)),
+        })
+        .collect();
+    let g = S256Point::new_g();
+    let commitments = coefficients
+        .into_iter()
+        .map(|a| g.clone().rmul(a))
+        .collect();
+    Ok((result, commitments))
+}
+
+// Horner's method, mod n
+fn evaluate(coefficients: &[BigInt], x: &BigInt) -> BigInt {
+    let n = order();
+    coefficients
+        .iter()
+        .rev()
+        .fold(BigInt::zero(), |acc, a| (acc * x + a) % &n)
+}
+
+// Lagrange interpolation at x = 0. The result is only the original key when
+// at least `threshold` shares are given.
+pub fn combine(shares: &[Share]) -> Result<PrivateKey, ShamirError> {
+    if shares.is_empty() {
+        return Err(ShamirError::NoShares);
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 || shares[..i].iter().any(|s| s.index == share.index) {
+            return Err(ShamirError::InvalidShareIndex(share.index));
+        }
+    }
+
+    let n = order();
+    let mut secret = BigInt::zero();
+    for share in shares {
+        let xi = BigInt::from(share.index);
+        let mut num = BigInt::one();
+        let mut den = BigInt::one();
+        for other in shares.iter().filter(|s| s.index != share.index) {
+            let xj = BigInt::from(other.index);
+            num = num * &xj % &n;
+            den = den * ((&xj - &xi + &n) % &n) % &n;
+        }
+        secret = (secret + &share.value * num * inverse(&den)) % &n;
+    }
+    Ok(PrivateKey::new(secret))
+}
+
+// Feldman check: value * G == sum_j C_j * index^j
+pub fn verify_share(share: &Share, commitments: &[S256Point]) -> bool {
+    let n = order();
+    let x = BigInt::from(share.index);
+    let mut power = BigInt::one();
+    let mut expected = S256Point::new(None, None);
+    for c in commitments {
+        expected = expected + c.clone().rmul(power.clone());
+        power = power * &x % &n;
+    }
+    S256Point::new_g().rmul(share.value.clone()) == expected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> PrivateKey {
+        PrivateKey::new(BigInt::parse_bytes(b"54321deadbeef", 16).unwrap())
+    }
+
+    #[test]
+    fn split_and_combine() {
+        let (shares, _) = split(&key(), 2, 3).unwrap();
+        assert_eq!(shares.len(), 3);
+        assert_eq!(combine(&shares[..2]).unwrap(), key());
+        assert_eq!(combine(&[shares[2].clone(), shares[0].clone()]).unwrap(), key());
+        assert_eq!(combine(&shares).unwrap(), key());
+    }
+
+    #[test]
+    fn combine_below_threshold() {
+        let (shares, _) = split(&key(), 3, 5).unwrap();
+        assert_ne!(combine(&shares[1..3]).unwrap(), key());
+        assert_eq!(combine(&shares[1..4]).unwrap(), key());
+    }
+
+    #[test]
+    fn verify_share_test() {
+        let (shares, commitments) = split(&key(), 2, 3).unwrap();
+        assert_eq!(commitments[0], key().point);
+        assert_eq!(true, verify_share(&shares[1], &commitments));
+
+        let tampered = Share {
+            index: shares[1].index,
+            value: &shares[1].value + 1,
+        };
+        assert_eq!(false, verify_share(&tampered, &commitments));
+    }
+
+    #[test]
+    fn invalid_threshold() {
+        assert_eq!(split(&key(), 0, 3), Err(ShamirError::InvalidThreshold));
+        assert_eq!(split(&key(), 4, 3), Err(ShamirError::InvalidThreshold));
+    }
+
+    #[test]
+    fn invalid_shares() {
+        let share = Share {
+            index: 1,
+            value: BigInt::from(5u8),
+        };
+        assert_eq!(combine(&[]), Err(ShamirError::NoShares));
+        assert_eq!(
+            combine(&[share.clone(), share]),
+            Err(ShamirError::InvalidShareIndex(1))
+        );
+    }
+}
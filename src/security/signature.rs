@@ -1,4 +1,18 @@
-use num_bigint::BigInt;
+use crate::util::hex::{hex, FromHex, HexError, ToHex};
+use num_bigint::{BigInt, Sign};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SignatureError {
+    Hex(HexError),
+    // not a DER sequence of two positive, minimally encoded integers
+    InvalidDer,
+}
+
+impl From<HexError> for SignatureError {
+    fn from(e: HexError) -> Self {
+        SignatureError::Hex(e)
+    }
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Signature {
@@ -52,17 +66,62 @@ impl Signature {
         let sig_length = 2 + 2 + r_length + s_length;
 
         // remove all null bytes from rbin head
-        let mut result = Vec::<u8>::new();
-        result.push(marker);
-        result.push(sig_length);
-        result.push(r_marker);
-        result.push(r_length);
+        let mut result = vec![marker, sig_length, r_marker, r_length];
         result.extend(r_value_bytes);
         result.push(s_marker);
         result.push(s_length);
         result.extend(s_value_bytes);
         return result;
     }
+
+    // the inverse of der(); lengths must match exactly, with nothing left over
+    pub fn parse_der(der: &[u8]) -> Result<Self, SignatureError> {
+        match der {
+            [0x30, len, rest @ ..] if *len as usize == rest.len() => {
+                let (r, rest) = parse_der_int(rest)?;
+                let (s, rest) = parse_der_int(rest)?;
+                if !rest.is_empty() {
+                    return Err(SignatureError::InvalidDer);
+                }
+                Ok(Self::new(r, s))
+            }
+            _ => Err(SignatureError::InvalidDer),
+        }
+    }
+}
+
+// returns the integer and the bytes after it
+fn parse_der_int(bytes: &[u8]) -> Result<(BigInt, &[u8]), SignatureError> {
+    let (value, rest) = match bytes {
+        [0x02, len, rest @ ..] if (*len as usize) <= rest.len() => rest.split_at(*len as usize),
+        _ => return Err(SignatureError::InvalidDer),
+    };
+    match value {
+        // empty or negative
+        [] => return Err(SignatureError::InvalidDer),
+        [first, ..] if first & 0x80 != 0 => return Err(SignatureError::InvalidDer),
+        // r and s are at least 1
+        [0x00] => return Err(SignatureError::InvalidDer),
+        // a leading zero is only allowed to clear the sign bit
+        [0x00, second, ..] if second & 0x80 == 0 => return Err(SignatureError::InvalidDer),
+        _ => {}
+    }
+    Ok((BigInt::from_bytes_be(Sign::Plus, value), rest))
+}
+
+// DER encoded
+impl ToHex for Signature {
+    fn to_hex(&self) -> String {
+        hex(&self.der())
+    }
+}
+
+impl FromHex for Signature {
+    type Error = SignatureError;
+
+    fn from_hex(s: &str) -> Result<Self, SignatureError> {
+        Self::parse_der(&Vec::from_hex(s)?)
+    }
 }
 
 #[cfg(test)]
@@ -70,7 +129,6 @@ mod tests {
     use super::*;
     use crate::ecc::s256_field::S256Field;
     use crate::ecc::s256_point::S256Point;
-    use crate::util::hex::hex;
 
     #[test]
     fn signature_test1() {
@@ -131,4 +189,58 @@ mod tests {
         let sig = Signature::new(r, s);
         assert_eq!(hex(&sig.der()), "3045022037206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c60221008ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec");
     }
+
+    #[test]
+    fn parse_der_test() {
+        let der_hex = "3045022037206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c60221008ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec";
+        let sig = Signature::from_hex(der_hex).unwrap();
+        assert_eq!(
+            sig.r,
+            BigInt::parse_bytes(
+                b"37206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6",
+                16
+            )
+            .unwrap()
+        );
+        assert_eq!(sig.to_hex(), der_hex);
+        assert_eq!(Signature::parse_der(&sig.der()).unwrap(), sig);
+
+        let sig = Signature::new(BigInt::from(1u8), BigInt::from(0x80u8));
+        assert_eq!(sig.to_hex(), "300702010102020080");
+        assert_eq!(Signature::from_hex(&sig.to_hex()).unwrap(), sig);
+    }
+
+    #[test]
+    fn parse_der_invalid() {
+        for der_hex in [
+            "",
+            // wrong sequence tag
+            "3106020101020101",
+            // sequence length too long
+            "3007020101020101",
+            // trailing byte
+            "300602010102010100",
+            // negative s
+            "3006020101020181",
+            // zero padding r needlessly
+            "300702020001020101",
+            // empty r
+            "30050200020101",
+            // zero r
+            "3006020100020101",
+            // s length past the end
+            "3006020101020201",
+        ] {
+            assert_eq!(
+                Signature::from_hex(der_hex),
+                Err(SignatureError::InvalidDer),
+                "{}",
+                der_hex
+            );
+        }
+        assert_eq!(
+            Signature::from_hex("300"),
+            Err(SignatureError::Hex(HexError::OddLength(3)))
+        );
+    }
 }
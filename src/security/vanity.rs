@@ -0,0 +1,313 @@
+// Vanity P2PKH address search.
+// Candidates are walked as P, P + G, P + 2G, ... from a random start, which costs
+// one point addition per address instead of a full scalar multiplication.
+use super::private_key::PrivateKey;
+use super::public_key::PublicKey;
+use crate::ecc::s256_point::S256Point;
+use crate::network::Network;
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::One;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum VanityError {
+    // holds the position of the character that is not in the base58 alphabet
+    InvalidCharacter(usize),
+    // no address for the network can start with the prefix
+    ImpossiblePrefix,
+    // every worker thread stopped (panicked) before finding a match
+    WorkersStopped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub attempts: u64,
+    pub elapsed: Duration,
+    // expected number of attempts
+    pub difficulty: f64,
+}
+
+impl Progress {
+    // chance that a match would have been found by now
+    pub fn probability(&self) -> f64 {
+        1.0 - (1.0 - 1.0 / self.difficulty).powf(self.attempts as f64)
+    }
+
+    // 0.0 until any time has passed
+    pub fn keys_per_second(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.attempts as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VanitySearch {
+    prefix: String,
+    compressed: bool,
+    network: Network,
+    difficulty: f64,
+    threads: usize,
+    progress_interval: Duration,
+}
+
+// Fraction of the 25 byte addresses (version || hash160 || checksum) with the given version
+// byte whose base58 form starts with prefix. Each leading '1' stands for a zero byte, the rest
+// of the prefix for a range of values of each possible encoded length.
+fn probability(prefix: &str, version: u8) -> f64 {
+    let zeros = prefix.chars().take_while(|c| *c == '1').count();
+    let rest: Vec<f64> = prefix[zeros..]
+        .chars()
+        .map(|c| BASE58_ALPHABET.find(c).unwrap() as f64)
+        .collect();
+
+    // values with exactly `zeros` leading zero bytes, or at least that many when nothing follows
+    let mut low = version as f64 * 2f64.powi(192);
+    let mut high = (version as f64 + 1.0) * 2f64.powi(192);
+    if zeros > 25 {
+        return 0.0;
+    }
+    high = high.min(2f64.powi(8 * (25 - zeros as i32)));
+    if !rest.is_empty() {
+        low = low.max(2f64.powi(8 * (24 - zeros as i32)));
+    }
+    if low >= high {
+        return 0.0;
+    }
+    if rest.is_empty() {
+        return (high - low) / 2f64.powi(192);
+    }
+
+    let value = rest.iter().fold(0.0, |acc, digit| acc * 58.0 + digit);
+    let mut total = 0.0;
+    // a 25 byte value has at most 35 base58 digits
+    for digits in rest.len()..=35 {
+        let scale = 58f64.powi((digits - rest.len()) as i32);
+        let start = (value * scale).max(58f64.powi(digits as i32 - 1)).max(low);
+        let end = ((value + 1.0) * scale)
+            .min(58f64.powi(digits as i32))
+            .min(high);
+        if end > start {
+            total += end - start;
+        }
+    }
+    total / 2f64.powi(192)
+}
+
+impl VanitySearch {
+    pub fn new(prefix: &str, compressed: bool, network: Network) -> Result<Self, VanityError> {
+        if let Some(position) = prefix.chars().position(|c| !BASE58_ALPHABET.contains(c)) {
+            return Err(VanityError::InvalidCharacter(position));
+        }
+        let probability = probability(prefix, network.p2pkh_prefix());
+        if probability == 0.0 {
+            return Err(VanityError::ImpossiblePrefix);
+        }
+        Ok(Self {
+            prefix: prefix.to_string(),
+            compressed,
+            network,
+            difficulty: 1.0 / probability,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            progress_interval: Duration::from_secs(1),
+        })
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn progress_interval(mut self, interval: Duration) -> Self {
+        self.progress_interval = interval;
+        self
+    }
+
+    pub fn difficulty(&self) -> f64 {
+        self.difficulty
+    }
+
+    pub fn search<F: FnMut(&Progress)>(&self, on_progress: F) -> Result<PrivateKey, VanityError> {
+        let secret = self.search_from(S256Point::new(None, None), on_progress)?;
+        Ok(PrivateKey::new(secret))
+    }
+
+    // Split-key mode: searches for k such that base + k * G has the prefix, where base is
+    // someone else's public key. Only the owner of base's private key can use the result,
+    // via combine_split_key.
+    pub fn search_split<F: FnMut(&Progress)>(
+        &self,
+        base: &S256Point,
+        on_progress: F,
+    ) -> Result<BigInt, VanityError> {
+        self.search_from(base.clone(), on_progress)
+    }
+
+    fn search_from<F: FnMut(&Progress)>(
+        &self,
+        base: S256Point,
+        mut on_progress: F,
+    ) -> Result<BigInt, VanityError> {
+        let found = Arc::new(AtomicBool::new(false));
+        let attempts = Arc::new(AtomicU64::new(0));
+        let (sender, receiver) = mpsc::channel();
+
+        let workers: Vec<_> = (0..self.threads)
+            .map(|_| {
+                let (found, attempts, sender) = (found.clone(), attempts.clone(), sender.clone());
+                let (base, search) = (base.clone(), self.clone());
+                thread::spawn(move || search.worker(base, &found, &attempts, sender))
+            })
+            .collect();
+        drop(sender);
+
+        let start = Instant::now();
+        let result = loop {
+            match receiver.recv_timeout(self.progress_interval) {
+                Ok(k) => break Ok(k),
+                Err(mpsc::RecvTimeoutError::Timeout) => on_progress(&Progress {
+                    attempts: attempts.load(Ordering::Relaxed),
+                    elapsed: start.elapsed(),
+                    difficulty: self.difficulty,
+                }),
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    break Err(VanityError::WorkersStopped)
+                }
+            }
+        };
+        found.store(true, Ordering::Relaxed);
+        for worker in workers {
+            // a panicked worker either left the others to find the key or caused WorkersStopped
+            let _ = worker.join();
+        }
+        result
+    }
+
+    fn worker(
+        &self,
+        base: S256Point,
+        found: &AtomicBool,
+        attempts: &AtomicU64,
+        sender: mpsc::Sender<BigInt>,
+    ) {
+        let g = S256Point::new_g();
+        let n = g.n.clone();
+        let mut k = rand::thread_rng().gen_bigint_range(&BigInt::one(), &n);
+        let mut point = base + g.clone().rmul(k.clone());
+
+        while !found.load(Ordering::Relaxed) {
+            let matches = PublicKey::new(point.clone(), self.compressed)
+                .is_ok_and(|pubkey| pubkey.address(self.network).starts_with(&self.prefix));
+            if matches {
+                // the receiver is gone once another worker has won
+                let _ = sender.send(k % &n);
+                return;
+            }
+            point = point + &g;
+            k += 1;
+            attempts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// the private key for the address found by search_split on key.point
+pub fn combine_split_key(key: &PrivateKey, partial: &BigInt) -> PrivateKey {
+    let n = S256Point::new(None, None).n;
+    PrivateKey::new((&key.secret + partial) % n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difficulty_test() {
+        let difficulty = |prefix| {
+            VanitySearch::new(prefix, false, Network::Mainnet)
+                .unwrap()
+                .difficulty()
+        };
+        assert_eq!(difficulty("1"), 1.0);
+        assert_eq!(difficulty("11"), 256.0);
+        // same estimate as vanitygen
+        assert_eq!(difficulty("1Love").round(), 4476343.0);
+        // each further character multiplies the difficulty by 58
+        let ratio = difficulty("1Bitc") / difficulty("1Bit");
+        assert_eq!(true, (ratio - 58.0).abs() < 0.01);
+        assert_eq!(true, difficulty("1A") < difficulty("1z"));
+    }
+
+    #[test]
+    fn invalid_prefix() {
+        assert_eq!(
+            VanitySearch::new("1Bi0ck", false, Network::Mainnet).unwrap_err(),
+            VanityError::InvalidCharacter(3)
+        );
+        assert_eq!(
+            VanitySearch::new("2abc", false, Network::Mainnet).unwrap_err(),
+            VanityError::ImpossiblePrefix
+        );
+        assert_eq!(
+            VanitySearch::new("1abc", false, Network::Testnet3).unwrap_err(),
+            VanityError::ImpossiblePrefix
+        );
+        assert_eq!(
+            true,
+            VanitySearch::new("mx", true, Network::Testnet3).is_ok()
+        );
+    }
+
+    #[test]
+    fn search_test() {
+        let search = VanitySearch::new("1A", true, Network::Mainnet)
+            .unwrap()
+            .threads(2);
+        let prv = search.search(|_| {}).unwrap();
+        assert_eq!(prv.point.address(true, Network::Mainnet)[..2], *"1A");
+    }
+
+    #[test]
+    fn search_split_test() {
+        let prv = PrivateKey::new(BigInt::from(5003));
+        let search = VanitySearch::new("1B", false, Network::Mainnet)
+            .unwrap()
+            .threads(2);
+        let partial = search.search_split(&prv.point, |_| {}).unwrap();
+        let combined = combine_split_key(&prv, &partial);
+        assert_eq!(combined.point.address(false, Network::Mainnet)[..2], *"1B");
+    }
+
+    #[test]
+    fn progress_test() {
+        let progress = Progress {
+            attempts: 100,
+            elapsed: Duration::from_secs(2),
+            difficulty: 100.0,
+        };
+        assert_eq!(progress.keys_per_second(), 50.0);
+        assert_eq!(true, (progress.probability() - 0.634).abs() < 0.001);
+
+        let progress = Progress {
+            elapsed: Duration::ZERO,
+            ..progress
+        };
+        assert_eq!(progress.keys_per_second(), 0.0);
+    }
+
+    #[test]
+    fn workers_stopped() {
+        let mut search = VanitySearch::new("1A", true, Network::Mainnet).unwrap();
+        // with no workers the channel is disconnected right away, as if they had all panicked
+        search.threads = 0;
+        assert_eq!(
+            search.search(|_| {}).unwrap_err(),
+            VanityError::WorkersStopped
+        );
+    }
+}
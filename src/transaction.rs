@@ -1 +1,3 @@
+pub mod address;
+pub mod amount;
 pub mod tx;
@@ -0,0 +1,445 @@
+// Addresses of every standard output type, parsed from and printed as the strings users
+// paste, and mapped to and from the scriptPubKey they pay to.
+use crate::ecc::s256_point::S256Point;
+use crate::network::Network;
+use crate::security::public_key::PublicKey;
+use crate::util::base58::{decode_base58_check_version, encode_base58_checksum, Base58Error};
+use crate::util::bech32::{decode_segwit_address, encode_segwit_address, Bech32Error};
+use crate::util::hash_types::Hash160;
+use bitcoin_hashes::{sha256, Hash};
+use std::fmt;
+use std::str::FromStr;
+
+const OP_0: u8 = 0x00;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_DUP: u8 = 0x76;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_HASH160: u8 = 0xa9;
+const OP_CHECKSIG: u8 = 0xac;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AddressError {
+    Base58(Base58Error),
+    Bech32(Bech32Error),
+    // base58 addresses carry a 20 byte hash
+    InvalidLength(usize),
+    UnknownVersion(u8),
+    // segwit outputs only commit to compressed keys
+    UncompressedKey,
+    // the script is not one of the standard output types
+    UnknownScript,
+}
+
+impl From<Base58Error> for AddressError {
+    fn from(e: Base58Error) -> Self {
+        AddressError::Base58(e)
+    }
+}
+
+impl From<Bech32Error> for AddressError {
+    fn from(e: Bech32Error) -> Self {
+        AddressError::Bech32(e)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Address {
+    P2pkh {
+        hash: [u8; 20],
+        network: Network,
+    },
+    P2sh {
+        hash: [u8; 20],
+        network: Network,
+    },
+    P2wpkh {
+        hash: [u8; 20],
+        network: Network,
+    },
+    P2wsh {
+        hash: [u8; 32],
+        network: Network,
+    },
+    // x-only output key, already tweaked with any script tree
+    P2tr {
+        output_key: [u8; 32],
+        network: Network,
+    },
+    // witness versions without a defined output type yet, including non-32 byte version 1
+    Witness {
+        version: u8,
+        program: Vec<u8>,
+        network: Network,
+    },
+}
+
+impl Address {
+    pub fn p2pkh(pubkey: &PublicKey, network: Network) -> Self {
+        let hash = pubkey.hash160().to_byte_array();
+        Address::P2pkh { hash, network }
+    }
+
+    pub fn p2sh(redeem_script: &[u8], network: Network) -> Self {
+        let hash = Hash160::hash(redeem_script).to_byte_array();
+        Address::P2sh { hash, network }
+    }
+
+    pub fn p2wpkh(pubkey: &PublicKey, network: Network) -> Result<Self, AddressError> {
+        if !pubkey.compressed {
+            return Err(AddressError::UncompressedKey);
+        }
+        let hash = pubkey.hash160().to_byte_array();
+        Ok(Address::P2wpkh { hash, network })
+    }
+
+    pub fn p2wsh(witness_script: &[u8], network: Network) -> Self {
+        let hash = sha256::Hash::hash(witness_script).into_inner();
+        Address::P2wsh { hash, network }
+    }
+
+    pub fn p2tr(output_key: &S256Point, network: Network) -> Self {
+        let output_key = output_key.xonly().try_into().unwrap();
+        Address::P2tr {
+            output_key,
+            network,
+        }
+    }
+
+    pub fn network(&self) -> Network {
+        match self {
+            Address::P2pkh { network, .. }
+            | Address::P2sh { network, .. }
+            | Address::P2wpkh { network, .. }
+            | Address::P2wsh { network, .. }
+            | Address::P2tr { network, .. }
+            | Address::Witness { network, .. } => *network,
+        }
+    }
+
+    // Whether the address can be used on network. Parsing can't tell the test networks
+    // apart, so this compares the encoding rather than network().
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        let own = self.network();
+        match self {
+            Address::P2pkh { .. } | Address::P2sh { .. } => {
+                own.p2pkh_prefix() == network.p2pkh_prefix()
+            }
+            _ => own.bech32_hrp() == network.bech32_hrp(),
+        }
+    }
+
+    // (version, program) for segwit outputs
+    fn witness_program(&self) -> Option<(u8, &[u8])> {
+        match self {
+            Address::P2wpkh { hash, .. } => Some((0, hash)),
+            Address::P2wsh { hash, .. } => Some((0, hash)),
+            Address::P2tr { output_key, .. } => Some((1, output_key)),
+            Address::Witness {
+                version, program, ..
+            } => Some((*version, program)),
+            _ => None,
+        }
+    }
+
+    pub fn script_pubkey(&self) -> Vec<u8> {
+        match self {
+            Address::P2pkh { hash, .. } => {
+                let mut script = vec![OP_DUP, OP_HASH160, 20];
+                script.extend(hash);
+                script.extend([OP_EQUALVERIFY, OP_CHECKSIG]);
+                script
+            }
+            Address::P2sh { hash, .. } => {
+                let mut script = vec![OP_HASH160, 20];
+                script.extend(hash);
+                script.push(OP_EQUAL);
+                script
+            }
+            _ => {
+                let (version, program) = self.witness_program().unwrap();
+                let op = if version == 0 {
+                    OP_0
+                } else {
+                    OP_1 + version - 1
+                };
+                let mut script = vec![op, program.len() as u8];
+                script.extend(program);
+                script
+            }
+        }
+    }
+
+    pub fn from_script(script: &[u8], network: Network) -> Result<Self, AddressError> {
+        match script {
+            [OP_DUP, OP_HASH160, 20, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG]
+                if hash.len() == 20 =>
+            {
+                Ok(Address::P2pkh {
+                    hash: hash.try_into().unwrap(),
+                    network,
+                })
+            }
+            [OP_HASH160, 20, hash @ .., OP_EQUAL] if hash.len() == 20 => Ok(Address::P2sh {
+                hash: hash.try_into().unwrap(),
+                network,
+            }),
+            [op, len, program @ ..]
+                if (*op == OP_0 || (OP_1..=OP_16).contains(op))
+                    && *len as usize == program.len()
+                    && (2..=40).contains(&program.len()) =>
+            {
+                let version = if *op == OP_0 { 0 } else { op - OP_1 + 1 };
+                Self::from_witness_program(version, program, network)
+            }
+            _ => Err(AddressError::UnknownScript),
+        }
+    }
+
+    fn from_witness_program(
+        version: u8,
+        program: &[u8],
+        network: Network,
+    ) -> Result<Self, AddressError> {
+        Ok(match (version, program.len()) {
+            (0, 20) => Address::P2wpkh {
+                hash: program.try_into().unwrap(),
+                network,
+            },
+            (0, 32) => Address::P2wsh {
+                hash: program.try_into().unwrap(),
+                network,
+            },
+            (0, len) => return Err(Bech32Error::InvalidProgramLength(len).into()),
+            (1, 32) => Address::P2tr {
+                output_key: program.try_into().unwrap(),
+                network,
+            },
+            _ => Address::Witness {
+                version,
+                program: program.to_vec(),
+                network,
+            },
+        })
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let network = self.network();
+        let s = match self {
+            Address::P2pkh { hash, .. } => {
+                let mut bytes = vec![network.p2pkh_prefix()];
+                bytes.extend(hash);
+                encode_base58_checksum(&bytes)
+            }
+            Address::P2sh { hash, .. } => {
+                let mut bytes = vec![network.p2sh_prefix()];
+                bytes.extend(hash);
+                encode_base58_checksum(&bytes)
+            }
+            _ => {
+                let (version, program) = self.witness_program().unwrap();
+                encode_segwit_address(network.bech32_hrp(), version, program).unwrap()
+            }
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        let segwit_network = Network::ALL
+            .into_iter()
+            .find(|n| lower.starts_with(&format!("{}1", n.bech32_hrp())));
+        if let Some(network) = segwit_network {
+            let (_, version, program) = decode_segwit_address(s)?;
+            return Self::from_witness_program(version, &program, network);
+        }
+
+        let (version, payload) = decode_base58_check_version(s)?;
+        let hash: [u8; 20] = payload
+            .try_into()
+            .map_err(|payload: Vec<u8>| AddressError::InvalidLength(payload.len()))?;
+        if let Some(network) = Network::from_p2pkh_prefix(version) {
+            Ok(Address::P2pkh { hash, network })
+        } else if let Some(network) = Network::from_p2sh_prefix(version) {
+            Ok(Address::P2sh { hash, network })
+        } else {
+            Err(AddressError::UnknownVersion(version))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::private_key::PrivateKey;
+    use crate::util::hex::{hex, FromHex};
+    use num_bigint::BigInt;
+
+    #[test]
+    fn p2pkh_test() {
+        let pubkey = PrivateKey::new(BigInt::from(1)).public_key(true);
+        let address = Address::p2pkh(&pubkey, Network::Mainnet);
+        assert_eq!(address.to_string(), "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+        assert_eq!(
+            hex(&address.script_pubkey()),
+            "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"
+        );
+        assert_eq!(
+            Address::from_str("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH").unwrap(),
+            address
+        );
+
+        let pubkey = PrivateKey::new(BigInt::from(5002)).public_key(false);
+        let address = Address::p2pkh(&pubkey, Network::Testnet3);
+        assert_eq!(address.to_string(), "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA");
+        assert_eq!(address.to_string(), pubkey.address(Network::Testnet3));
+        assert_eq!(true, address.is_valid_for_network(Network::Signet));
+        assert_eq!(true, address.is_valid_for_network(Network::Regtest));
+        assert_eq!(false, address.is_valid_for_network(Network::Mainnet));
+    }
+
+    #[test]
+    fn p2sh_test() {
+        let address = Address::from_str("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy").unwrap();
+        assert_eq!(address.network(), Network::Mainnet);
+        assert_eq!(
+            hex(&address.script_pubkey()),
+            "a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87"
+        );
+        assert_eq!(
+            Address::from_script(&address.script_pubkey(), Network::Mainnet).unwrap(),
+            address
+        );
+        let testnet = Address::p2sh(&[OP_1], Network::Testnet3).to_string();
+        assert_eq!(&testnet[..1], "2");
+    }
+
+    #[test]
+    fn segwit_test() {
+        let pubkey = PrivateKey::new(BigInt::from(1)).public_key(true);
+        let p2wpkh = Address::p2wpkh(&pubkey, Network::Mainnet).unwrap();
+        assert_eq!(
+            p2wpkh.to_string(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+        assert_eq!(
+            hex(&p2wpkh.script_pubkey()),
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6"
+        );
+        assert_eq!(
+            Address::p2wpkh(
+                &PrivateKey::new(BigInt::from(1)).public_key(false),
+                Network::Mainnet
+            ),
+            Err(AddressError::UncompressedKey)
+        );
+
+        // <G> OP_CHECKSIG, from BIP173
+        let mut witness_script = vec![33];
+        witness_script.extend(pubkey.sec());
+        witness_script.push(OP_CHECKSIG);
+        let p2wsh = Address::p2wsh(&witness_script, Network::Testnet3);
+        assert_eq!(
+            p2wsh.to_string(),
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7"
+        );
+
+        let p2tr = Address::p2tr(&S256Point::new_g(), Network::Mainnet);
+        assert_eq!(
+            p2tr.to_string(),
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"
+        );
+        assert_eq!(
+            hex(&p2tr.script_pubkey()),
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        );
+
+        let regtest = Address::p2wpkh(&pubkey, Network::Regtest).unwrap();
+        assert_eq!(&regtest.to_string()[..5], "bcrt1");
+        assert_eq!(
+            Address::from_str(&regtest.to_string()).unwrap().network(),
+            Network::Regtest
+        );
+        assert_eq!(false, regtest.is_valid_for_network(Network::Testnet4));
+    }
+
+    #[test]
+    fn from_str_test() {
+        for s in [
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+            "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA",
+            "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs",
+        ] {
+            let address = Address::from_str(s).unwrap();
+            assert_eq!(address.to_string(), s);
+            assert_eq!(
+                Address::from_script(&address.script_pubkey(), address.network()).unwrap(),
+                address
+            );
+        }
+        // uppercase bech32 is valid, but printed in lowercase
+        let address = Address::from_str("BC1SW50QGDZ25J").unwrap();
+        assert_eq!(
+            address,
+            Address::Witness {
+                version: 16,
+                program: vec![0x75, 0x1e],
+                network: Network::Mainnet,
+            }
+        );
+        assert_eq!(address.to_string(), "bc1sw50qgdz25j");
+        assert_eq!(hex(&address.script_pubkey()), "6002751e");
+    }
+
+    #[test]
+    fn invalid_test() {
+        assert_eq!(
+            Address::from_str("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMJ"),
+            Err(AddressError::Base58(Base58Error::InvalidChecksum))
+        );
+        // a WIF key is valid base58check, but not an address
+        assert_eq!(
+            Address::from_str("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S53a"),
+            Err(AddressError::InvalidLength(33))
+        );
+        let mut bytes = vec![0x30];
+        bytes.extend([0; 20]);
+        assert_eq!(
+            Address::from_str(&encode_base58_checksum(&bytes)),
+            Err(AddressError::UnknownVersion(0x30))
+        );
+        assert_eq!(
+            Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh"),
+            Err(AddressError::Bech32(Bech32Error::InvalidVariant(
+                crate::util::bech32::Variant::Bech32m
+            )))
+        );
+        assert_eq!(
+            Address::from_script(&[OP_1], Network::Mainnet),
+            Err(AddressError::UnknownScript)
+        );
+        // the push length must match the program
+        assert_eq!(
+            Address::from_script(&[OP_0, 20, 0x75, 0x1e], Network::Mainnet),
+            Err(AddressError::UnknownScript)
+        );
+        assert_eq!(
+            Address::from_script(
+                &Vec::from_hex("0010751e76e8199196d454941c45d1b3a323").unwrap(),
+                Network::Mainnet
+            ),
+            Err(AddressError::Bech32(Bech32Error::InvalidProgramLength(16)))
+        );
+    }
+}
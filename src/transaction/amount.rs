@@ -0,0 +1,504 @@
+// Amounts are whole satoshis. Strings are always "<number> <denomination>", parsed as decimals
+// so "0.1 BTC" is exactly 10,000,000 sat rather than a float that rounds to it.
+use crate::util::encode::{Decodable, Encodable, EncodeError};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseAmountError {
+    // not a plain decimal number
+    InvalidFormat,
+    // more decimal places than the denomination has, e.g. "1.5 sat"
+    TooPrecise,
+    Negative,
+    // above MAX_MONEY
+    TooBig,
+    MissingDenomination,
+    UnknownDenomination(String),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Denomination {
+    Bitcoin,
+    MilliBitcoin,
+    // a millionth of a bitcoin, 100 sat
+    Bit,
+    Satoshi,
+}
+
+impl Denomination {
+    // decimal places below one unit
+    fn precision(self) -> usize {
+        match self {
+            Denomination::Bitcoin => 8,
+            Denomination::MilliBitcoin => 5,
+            Denomination::Bit => 2,
+            Denomination::Satoshi => 0,
+        }
+    }
+}
+
+impl fmt::Display for Denomination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Denomination::Bitcoin => "BTC",
+            Denomination::MilliBitcoin => "mBTC",
+            Denomination::Bit => "bits",
+            Denomination::Satoshi => "sat",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Denomination {
+    type Err = ParseAmountError;
+
+    // case matters for mBTC, which would read as a megabitcoin in capitals
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "BTC" | "btc" => Ok(Denomination::Bitcoin),
+            "mBTC" | "mbtc" => Ok(Denomination::MilliBitcoin),
+            "bits" | "bit" | "uBTC" | "ubtc" => Ok(Denomination::Bit),
+            "sat" | "sats" | "satoshi" | "satoshis" => Ok(Denomination::Satoshi),
+            _ => Err(ParseAmountError::UnknownDenomination(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Amount(u64);
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SignedAmount(i64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const ONE_SAT: Amount = Amount(1);
+    pub const ONE_BTC: Amount = Amount(100_000_000);
+    // every bitcoin that will ever exist; no valid output or sum of outputs goes above it
+    pub const MAX_MONEY: Amount = Amount(21_000_000 * 100_000_000);
+
+    pub fn from_sat(sat: u64) -> Self {
+        Self(sat)
+    }
+
+    pub fn to_sat(self) -> u64 {
+        self.0
+    }
+
+    pub fn from_str_in(s: &str, denomination: Denomination) -> Result<Self, ParseAmountError> {
+        match parse_sat(s, denomination)? {
+            (true, 0) => Ok(Amount::ZERO),
+            (true, _) => Err(ParseAmountError::Negative),
+            (false, sat) => Ok(Self(sat)),
+        }
+    }
+
+    // the number only, without the denomination
+    pub fn to_string_in(self, denomination: Denomination) -> String {
+        format_sat(false, self.0, denomination)
+    }
+
+    // the checked operations fail on overflow and on results above MAX_MONEY
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_add(rhs.0).and_then(Self::in_range)
+    }
+
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_sub(rhs.0).and_then(Self::in_range)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<Amount> {
+        self.0.checked_mul(rhs).and_then(Self::in_range)
+    }
+
+    // rounds down
+    pub fn checked_div(self, rhs: u64) -> Option<Amount> {
+        self.0.checked_div(rhs).and_then(Self::in_range)
+    }
+
+    pub fn to_signed(self) -> Option<SignedAmount> {
+        i64::try_from(self.0).ok().map(SignedAmount)
+    }
+
+    fn in_range(sat: u64) -> Option<Amount> {
+        if sat > Self::MAX_MONEY.0 {
+            return None;
+        }
+        Some(Self(sat))
+    }
+}
+
+impl SignedAmount {
+    pub const ZERO: SignedAmount = SignedAmount(0);
+    pub const MAX_MONEY: SignedAmount = SignedAmount(Amount::MAX_MONEY.0 as i64);
+
+    pub fn from_sat(sat: i64) -> Self {
+        Self(sat)
+    }
+
+    pub fn to_sat(self) -> i64 {
+        self.0
+    }
+
+    pub fn from_str_in(s: &str, denomination: Denomination) -> Result<Self, ParseAmountError> {
+        let (negative, sat) = parse_sat(s, denomination)?;
+        // parse_sat already capped sat at MAX_MONEY
+        let sat = sat as i64;
+        Ok(Self(if negative { -sat } else { sat }))
+    }
+
+    pub fn to_string_in(self, denomination: Denomination) -> String {
+        format_sat(self.0 < 0, self.0.unsigned_abs(), denomination)
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    // the checked operations fail on overflow and on results beyond -MAX_MONEY..=MAX_MONEY
+    pub fn checked_abs(self) -> Option<SignedAmount> {
+        self.0.checked_abs().and_then(Self::in_range)
+    }
+
+    pub fn checked_add(self, rhs: SignedAmount) -> Option<SignedAmount> {
+        self.0.checked_add(rhs.0).and_then(Self::in_range)
+    }
+
+    pub fn checked_sub(self, rhs: SignedAmount) -> Option<SignedAmount> {
+        self.0.checked_sub(rhs.0).and_then(Self::in_range)
+    }
+
+    pub fn checked_mul(self, rhs: i64) -> Option<SignedAmount> {
+        self.0.checked_mul(rhs).and_then(Self::in_range)
+    }
+
+    // rounds toward zero
+    pub fn checked_div(self, rhs: i64) -> Option<SignedAmount> {
+        self.0.checked_div(rhs).and_then(Self::in_range)
+    }
+
+    // None when negative
+    pub fn to_unsigned(self) -> Option<Amount> {
+        u64::try_from(self.0).ok().map(Amount)
+    }
+
+    fn in_range(sat: i64) -> Option<SignedAmount> {
+        if sat.unsigned_abs() > Amount::MAX_MONEY.0 {
+            return None;
+        }
+        Some(Self(sat))
+    }
+}
+
+// returns (negative, sat); the magnitude is at most MAX_MONEY
+fn parse_sat(s: &str, denomination: Denomination) -> Result<(bool, u64), ParseAmountError> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || !whole.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(ParseAmountError::InvalidFormat);
+    }
+    let precision = denomination.precision();
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > precision {
+        return Err(ParseAmountError::TooPrecise);
+    }
+    // the number in satoshis is just the digits with the fraction padded to full precision
+    let digits = format!("{}{:0<width$}", whole, fraction, width = precision);
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok((negative, 0));
+    }
+    let sat: u64 = digits.parse().map_err(|_| ParseAmountError::TooBig)?;
+    if sat > Amount::MAX_MONEY.0 {
+        return Err(ParseAmountError::TooBig);
+    }
+    Ok((negative, sat))
+}
+
+// trailing zeros are dropped, so 150000 sat is "0.0015" in BTC
+fn format_sat(negative: bool, sat: u64, denomination: Denomination) -> String {
+    let sign = if negative { "-" } else { "" };
+    let precision = denomination.precision();
+    let unit = 10u64.pow(precision as u32);
+    let (whole, fraction) = (sat / unit, sat % unit);
+    if fraction == 0 {
+        return format!("{}{}", sign, whole);
+    }
+    let fraction = format!("{:0>width$}", fraction, width = precision);
+    format!("{}{}.{}", sign, whole, fraction.trim_end_matches('0'))
+}
+
+// "<number> <denomination>"
+fn split_denomination(s: &str) -> Result<(&str, Denomination), ParseAmountError> {
+    let (number, denomination) = s
+        .split_once(' ')
+        .ok_or(ParseAmountError::MissingDenomination)?;
+    Ok((number, denomination.parse()?))
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} BTC", self.to_string_in(Denomination::Bitcoin))
+    }
+}
+
+impl FromStr for Amount {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, denomination) = split_denomination(s)?;
+        Self::from_str_in(number, denomination)
+    }
+}
+
+impl fmt::Display for SignedAmount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} BTC", self.to_string_in(Denomination::Bitcoin))
+    }
+}
+
+impl FromStr for SignedAmount {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, denomination) = split_denomination(s)?;
+        Self::from_str_in(number, denomination)
+    }
+}
+
+// 8 bytes little-endian; decoding does not check MAX_MONEY, that is for transaction validation
+impl Encodable for Amount {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        self.0.consensus_encode(writer)
+    }
+}
+
+impl Decodable for Amount {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError> {
+        Ok(Self(u64::consensus_decode(reader)?))
+    }
+}
+
+// Stored in sat per 1000 weight units (sat/kWU). A virtual byte is 4 weight units, so
+// 1 sat/vB is 250 sat/kWU.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct FeeRate(u64);
+
+impl FeeRate {
+    pub const ZERO: FeeRate = FeeRate(0);
+
+    pub fn from_sat_per_kwu(sat_per_kwu: u64) -> Self {
+        Self(sat_per_kwu)
+    }
+
+    // None on overflow
+    pub fn from_sat_per_vb(sat_per_vb: u64) -> Option<Self> {
+        sat_per_vb.checked_mul(250).map(Self)
+    }
+
+    pub fn to_sat_per_kwu(self) -> u64 {
+        self.0
+    }
+
+    pub fn to_sat_per_vb_floor(self) -> u64 {
+        self.0 / 250
+    }
+
+    pub fn to_sat_per_vb_ceil(self) -> u64 {
+        self.0.div_ceil(250)
+    }
+
+    // rounded up so the rate is never undershot; None on overflow or above MAX_MONEY
+    pub fn fee_wu(self, weight: u64) -> Option<Amount> {
+        let sat = self.0.checked_mul(weight)?.div_ceil(1000);
+        Amount::in_range(sat)
+    }
+
+    pub fn fee_vb(self, vsize: u64) -> Option<Amount> {
+        self.fee_wu(vsize.checked_mul(4)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::encode::{deserialize, serialize};
+
+    #[test]
+    fn parse_test() {
+        let sat = Amount::from_sat;
+        assert_eq!("0.0015 BTC".parse::<Amount>(), Ok(sat(150_000)));
+        assert_eq!("1.5 mBTC".parse::<Amount>(), Ok(sat(150_000)));
+        assert_eq!("1500 bits".parse::<Amount>(), Ok(sat(150_000)));
+        assert_eq!("150000 sat".parse::<Amount>(), Ok(sat(150_000)));
+        assert_eq!(".5 BTC".parse::<Amount>(), Ok(sat(50_000_000)));
+        assert_eq!("1. btc".parse::<Amount>(), Ok(Amount::ONE_BTC));
+        // trailing zeros past the precision are harmless
+        assert_eq!("1.000000000 BTC".parse::<Amount>(), Ok(Amount::ONE_BTC));
+        assert_eq!("12.00 sat".parse::<Amount>(), Ok(sat(12)));
+        assert_eq!("21000000 BTC".parse::<Amount>(), Ok(Amount::MAX_MONEY));
+        assert_eq!("-0 BTC".parse::<Amount>(), Ok(Amount::ZERO));
+        assert_eq!(
+            Amount::from_str_in("0.00000001", Denomination::Bitcoin),
+            Ok(Amount::ONE_SAT)
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for (s, e) in [
+            ("1.5 sat", ParseAmountError::TooPrecise),
+            ("0.000000001 BTC", ParseAmountError::TooPrecise),
+            ("1.234 bits", ParseAmountError::TooPrecise),
+            ("-1 BTC", ParseAmountError::Negative),
+            ("21000000.00000001 BTC", ParseAmountError::TooBig),
+            ("99999999999999999999999 sat", ParseAmountError::TooBig),
+            (". BTC", ParseAmountError::InvalidFormat),
+            ("1,5 BTC", ParseAmountError::InvalidFormat),
+            ("1e3 sat", ParseAmountError::InvalidFormat),
+            ("+1 BTC", ParseAmountError::InvalidFormat),
+            (" BTC", ParseAmountError::InvalidFormat),
+            ("1.5", ParseAmountError::MissingDenomination),
+            (
+                "1.5 MBTC",
+                ParseAmountError::UnknownDenomination("MBTC".to_string()),
+            ),
+        ] {
+            assert_eq!(s.parse::<Amount>(), Err(e), "{}", s);
+        }
+    }
+
+    #[test]
+    fn format_test() {
+        let amount = Amount::from_sat(150_000);
+        assert_eq!(amount.to_string(), "0.0015 BTC");
+        assert_eq!(amount.to_string_in(Denomination::MilliBitcoin), "1.5");
+        assert_eq!(amount.to_string_in(Denomination::Bit), "1500");
+        assert_eq!(amount.to_string_in(Denomination::Satoshi), "150000");
+        assert_eq!(Amount::ONE_BTC.to_string(), "1 BTC");
+        assert_eq!(Amount::ONE_SAT.to_string(), "0.00000001 BTC");
+        assert_eq!(Amount::ZERO.to_string(), "0 BTC");
+        assert_eq!(
+            Amount::from_sat(123_456_789).to_string_in(Denomination::Bit),
+            "1234567.89"
+        );
+
+        for sat in [0, 1, 99, 150_000, 100_000_001, Amount::MAX_MONEY.to_sat()] {
+            let amount = Amount::from_sat(sat);
+            assert_eq!(amount.to_string().parse::<Amount>(), Ok(amount));
+            for denomination in [
+                Denomination::Bitcoin,
+                Denomination::MilliBitcoin,
+                Denomination::Bit,
+                Denomination::Satoshi,
+            ] {
+                let s = format!("{} {}", amount.to_string_in(denomination), denomination);
+                assert_eq!(s.parse::<Amount>(), Ok(amount));
+            }
+        }
+    }
+
+    #[test]
+    fn checked_arithmetic_test() {
+        let a = Amount::from_sat(3);
+        let b = Amount::from_sat(5);
+        assert_eq!(a.checked_add(b), Some(Amount::from_sat(8)));
+        assert_eq!(b.checked_sub(a), Some(Amount::from_sat(2)));
+        assert_eq!(a.checked_sub(b), None);
+        assert_eq!(a.checked_mul(4), Some(Amount::from_sat(12)));
+        assert_eq!(b.checked_div(2), Some(Amount::from_sat(2)));
+        assert_eq!(b.checked_div(0), None);
+
+        assert_eq!(
+            Amount::MAX_MONEY.checked_add(Amount::ZERO),
+            Some(Amount::MAX_MONEY)
+        );
+        assert_eq!(Amount::MAX_MONEY.checked_add(Amount::ONE_SAT), None);
+        assert_eq!(Amount::ONE_BTC.checked_mul(21_000_001), None);
+        assert_eq!(Amount::from_sat(u64::MAX).checked_mul(2), None);
+        assert_eq!(Amount::from_sat(u64::MAX).checked_add(a), None);
+    }
+
+    #[test]
+    fn signed_amount_test() {
+        let a = SignedAmount::from_sat(-150_000);
+        assert_eq!(a.to_string(), "-0.0015 BTC");
+        assert_eq!("-0.0015 BTC".parse::<SignedAmount>(), Ok(a));
+        assert_eq!("-1.5 mBTC".parse::<SignedAmount>(), Ok(a));
+        assert_eq!(true, a.is_negative());
+        assert_eq!(a.checked_abs(), Some(SignedAmount::from_sat(150_000)));
+        assert_eq!(a.to_unsigned(), None);
+        assert_eq!(
+            a.checked_abs().and_then(SignedAmount::to_unsigned),
+            Some(Amount::from_sat(150_000))
+        );
+        assert_eq!(SignedAmount::from_sat(i64::MIN).checked_abs(), None);
+        assert_eq!(SignedAmount::from_sat(-i64::MAX).checked_abs(), None);
+        assert_eq!(
+            Amount::from_sat(150_000).to_signed(),
+            Some(SignedAmount::from_sat(150_000))
+        );
+        assert_eq!(Amount::from_sat(u64::MAX).to_signed(), None);
+
+        let b = SignedAmount::from_sat(100_000);
+        assert_eq!(a.checked_add(b), Some(SignedAmount::from_sat(-50_000)));
+        assert_eq!(b.checked_sub(a), Some(SignedAmount::from_sat(250_000)));
+        assert_eq!(a.checked_mul(-2), Some(SignedAmount::from_sat(300_000)));
+        assert_eq!(a.checked_div(4), Some(SignedAmount::from_sat(-37_500)));
+
+        let min = SignedAmount::from_sat(-SignedAmount::MAX_MONEY.to_sat());
+        assert_eq!(min.to_string(), "-21000000 BTC");
+        assert_eq!(min.checked_sub(SignedAmount::from_sat(1)), None);
+        assert_eq!(SignedAmount::MAX_MONEY.checked_mul(2), None);
+        assert_eq!(SignedAmount::from_sat(i64::MIN).checked_mul(-1), None);
+        assert_eq!(
+            "-21000000.00000001 BTC".parse::<SignedAmount>(),
+            Err(ParseAmountError::TooBig)
+        );
+    }
+
+    #[test]
+    fn encode_test() {
+        let amount = Amount::from_sat(32454049);
+        let bytes = serialize(&amount);
+        assert_eq!(bytes, 32454049u64.to_le_bytes());
+        assert_eq!(deserialize::<Amount>(&bytes), Ok(amount));
+    }
+
+    #[test]
+    fn fee_rate_test() {
+        let rate = FeeRate::from_sat_per_vb(1).unwrap();
+        assert_eq!(rate.to_sat_per_kwu(), 250);
+        assert_eq!(rate.fee_vb(141), Some(Amount::from_sat(141)));
+        // 561 WU is 140.25 vB, which rounds up
+        assert_eq!(rate.fee_wu(561), Some(Amount::from_sat(141)));
+
+        let rate = FeeRate::from_sat_per_kwu(253);
+        assert_eq!(rate.to_sat_per_vb_floor(), 1);
+        assert_eq!(rate.to_sat_per_vb_ceil(), 2);
+        assert_eq!(rate.fee_wu(1000), Some(Amount::from_sat(253)));
+        assert_eq!(rate.fee_wu(1001), Some(Amount::from_sat(254)));
+
+        assert_eq!(
+            FeeRate::from_sat_per_vb(20).unwrap().fee_vb(250),
+            Some(Amount::from_sat(5_000))
+        );
+        assert_eq!(FeeRate::ZERO.fee_vb(1_000), Some(Amount::ZERO));
+        assert_eq!(FeeRate::from_sat_per_vb(u64::MAX), None);
+        assert_eq!(FeeRate::from_sat_per_kwu(u64::MAX).fee_wu(2), None);
+        assert_eq!(FeeRate::from_sat_per_vb(1).unwrap().fee_vb(u64::MAX), None);
+        // more than every bitcoin in existence
+        assert_eq!(
+            FeeRate::from_sat_per_kwu(u64::MAX / 1_000).fee_wu(1_000),
+            None
+        );
+    }
+}
@@ -1,165 +1,435 @@
-use bitcoin_hashes::Hash;
-
-use crate::util::hex::hex;
-use crate::util::reader::read;
-use crate::util::varint::{self, encode_varint};
+use super::amount::Amount;
+use crate::network::Network;
+use crate::util::encode::{deserialize, serialize, Decodable, Encodable, EncodeError};
+use crate::util::hash_types::{Txid, Wtxid};
+use crate::util::hex::{hex, FromHex, ToHex};
 use std::collections::HashMap;
-use std::io::Read;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// for each read from and write to the server
+const TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Tx {
-    version: u32,
-    tx_ins: Vec<TxIn>,
-    tx_outs: Vec<TxOut>,
-    locktime: u32,
-    testnet: bool,
+    pub version: u32,
+    pub tx_ins: Vec<TxIn>,
+    pub tx_outs: Vec<TxOut>,
+    pub locktime: u32,
+    // not serialized; the network to look previous transactions up on
+    pub network: Network,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct TxIn {
-    prev_tx: Vec<u8>,
-    prev_index: u32,
-    script_sig: Vec<u8>,
-    sequence: u32,
+    pub prev_tx: Txid,
+    pub prev_index: u32,
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+    // BIP144 witness stack; empty for inputs that don't spend segwit outputs. Tx writes it
+    // after the outputs, so TxIn's own encoding leaves it out
+    pub witness: Vec<Vec<u8>>,
 }
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct TxOut {
-    amount: u64,
-    script_pubkey: Vec<u8>,
+    pub amount: Amount,
+    pub script_pubkey: Vec<u8>,
 }
+
 #[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FetchError {
+    Io(io::ErrorKind),
+    // the status line of a non-200 response
+    Http(String),
+    Decode(EncodeError),
+    // the server returned a different transaction than the one asked for
+    IdMismatch,
+    // no server is known for the network
+    UnsupportedNetwork(Network),
+}
+
+impl From<io::Error> for FetchError {
+    fn from(e: io::Error) -> Self {
+        FetchError::Io(e.kind())
+    }
+}
+
+impl From<EncodeError> for FetchError {
+    fn from(e: EncodeError) -> Self {
+        FetchError::Decode(e)
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub struct TxFetcher {
-    cache: HashMap<Vec<u8>, Tx>,
+    cache: HashMap<Txid, Tx>,
 }
 
 impl Tx {
-    fn parse<R>(reader: R, testnet: bool) -> Self
+    // Decodable leaves network as Mainnet
+    pub fn parse<R>(reader: &mut R, network: Network) -> Result<Self, EncodeError>
     where
         R: Read,
     {
-        let buf = read(reader, 4);
-        let version = u32::from_le_bytes(buf.try_into().unwrap());
-        // parse Input portion
-        let num_inputs = varint::read_varint(reader);
-        let inputs: Vec<TxIn> = num_inputs.iter().map(|_| TxIn::parse(reader)).collect();
-        // parse Output portion
-        let num_outputs = varint::read_varint(reader);
-        let outputs: Vec<TxOut> = num_inputs.iter().map(|_| TxOut::parse(reader)).collect();
-        // parse Locktime portion
-        let locktime = u32::from_le_bytes(read(reader, 4).try_into().unwrap());
+        let mut tx = Self::consensus_decode(reader)?;
+        tx.network = network;
+        Ok(tx)
+    }
 
-        Self {
-            version,
-            tx_ins: inputs,
-            tx_outs: outputs,
-            locktime,
-            testnet,
-        }
+    // with witness data when there is any
+    pub fn serialize(&self) -> Vec<u8> {
+        serialize(self)
     }
 
-    fn serialize(self: &Self) -> Vec<u8> {
-        let mut result = u32::to_le_bytes(self.version).to_vec();
-        result.extend(encode_varint(self.tx_ins.len()).unwrap());
-        result.extend(
-            self.tx_ins
-                .iter()
-                .flat_map(|tx_in| tx_in.serialize())
-                .collect::<Vec<u8>>(),
-        );
-        result.extend(encode_varint(self.tx_outs.len()).unwrap());
-        result.extend(
-            self.tx_outs
-                .iter()
-                .flat_map(|tx_out| tx_out.serialize())
-                .collect::<Vec<u8>>(),
-        );
-        result.extend(u32::to_le_bytes(self.locktime).to_vec());
-        result
+    pub fn is_segwit(&self) -> bool {
+        self.tx_ins.iter().any(|tx_in| !tx_in.witness.is_empty())
+    }
+
+    // hash256 of the serialization without witness data
+    pub fn hash(&self) -> Txid {
+        let mut bytes = vec![];
+        // writing to a Vec can't fail
+        self.encode_without_witness(&mut bytes).unwrap();
+        Txid::hash(&bytes)
+    }
+
+    pub fn id(&self) -> String {
+        self.hash().to_string()
+    }
+
+    // hash256 of the serialization with witness data; the txid when there is none
+    pub fn wtxid(&self) -> Wtxid {
+        Wtxid::hash(&self.serialize())
+    }
+
+    fn encode_without_witness<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let mut len = self.version.consensus_encode(writer)?;
+        len += self.tx_ins.consensus_encode(writer)?;
+        len += self.tx_outs.consensus_encode(writer)?;
+        len += self.locktime.consensus_encode(writer)?;
+        Ok(len)
     }
 }
 
-impl TxIn {
-    // Takes a byte stream and parses the tx_input at the start. Returns a TxIn struct.
-    fn parse<R>(reader: R) -> Self
-    where
-        R: Read,
-    {
-        let prev_tx = read(reader, 32);
-        let prev_index = u32::from_le_bytes(read(reader, 4).try_into().unwrap());
-        let script_sig = Script::parse(reader);
-        let sequence = u32::from_le_bytes(read(reader, 4).try_into().unwrap());
-        Self {
-            prev_tx,
-            prev_index,
-            script_sig,
-            sequence,
+// BIP144: a transaction with any witness data is written as
+//   version | marker 0x00 | flag 0x01 | inputs | outputs | one witness stack per input | locktime
+// and one without any in the original format
+impl Encodable for Tx {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        if !self.is_segwit() {
+            return self.encode_without_witness(writer);
+        }
+        let mut len = self.version.consensus_encode(writer)?;
+        len += [0u8, 1].consensus_encode(writer)?;
+        len += self.tx_ins.consensus_encode(writer)?;
+        len += self.tx_outs.consensus_encode(writer)?;
+        for tx_in in &self.tx_ins {
+            len += tx_in.witness.consensus_encode(writer)?;
+        }
+        len += self.locktime.consensus_encode(writer)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for Tx {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError> {
+        let version = u32::consensus_decode(reader)?;
+        let mut tx_ins: Vec<TxIn> = Vec::consensus_decode(reader)?;
+        // an empty input list is the marker
+        let segwit = tx_ins.is_empty();
+        if segwit {
+            let flag = u8::consensus_decode(reader)?;
+            if flag != 1 {
+                return Err(EncodeError::InvalidSegwitFlag(flag));
+            }
+            tx_ins = Vec::consensus_decode(reader)?;
+        }
+        let tx_outs = Vec::consensus_decode(reader)?;
+        if segwit {
+            for tx_in in tx_ins.iter_mut() {
+                tx_in.witness = Vec::consensus_decode(reader)?;
+            }
+            if tx_ins.iter().all(|tx_in| tx_in.witness.is_empty()) {
+                return Err(EncodeError::EmptyWitness);
+            }
         }
+        Ok(Self {
+            version,
+            tx_ins,
+            tx_outs,
+            locktime: u32::consensus_decode(reader)?,
+            network: Network::Mainnet,
+        })
     }
+}
 
-    fn serialize(self: &Self) -> Vec<u8> {
-        let mut result: Vec<u8> = self.prev_tx.into_iter().rev().collect();
-        result.extend(u32::to_le_bytes(self.prev_index));
-        result.extend(self.script_sig.serialize());
-        result.extend(u32::to_le_bytes(self.sequence));
-        result
+impl Encodable for TxIn {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let mut len = self.prev_tx.consensus_encode(writer)?;
+        len += self.prev_index.consensus_encode(writer)?;
+        len += self.script_sig.consensus_encode(writer)?;
+        len += self.sequence.consensus_encode(writer)?;
+        Ok(len)
     }
 }
 
-impl TxOut {
-    // Takes a byte stream and parses the tx_output at the start.  Returns a TxOut struct.
-    fn parse<R>(reader: R) -> Self
-    where
-        R: Read,
-    {
-        let amount = u64::from_le_bytes(read(reader, 8).try_into().unwrap());
-        let script_pubkey = Script::parse(reader);
-        Self {
-            amount,
-            script_pubkey,
-        }
+impl Decodable for TxIn {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError> {
+        Ok(Self {
+            prev_tx: Txid::consensus_decode(reader)?,
+            prev_index: u32::consensus_decode(reader)?,
+            script_sig: Vec::consensus_decode(reader)?,
+            sequence: u32::consensus_decode(reader)?,
+            witness: vec![],
+        })
+    }
+}
+
+impl Encodable for TxOut {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let len = self.amount.consensus_encode(writer)?;
+        Ok(len + self.script_pubkey.consensus_encode(writer)?)
     }
+}
+
+impl Decodable for TxOut {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError> {
+        Ok(Self {
+            amount: Amount::consensus_decode(reader)?,
+            script_pubkey: Vec::consensus_decode(reader)?,
+        })
+    }
+}
 
-    fn serialize(self: &Self) -> Vec<u8> {
-        let mut result = u64::to_le_bytes(self.amount).to_vec();
-        result.extend(self.script_pubkey.serialize());
-        result
+// the serialization, as block explorers and bitcoind's getrawtransaction show it
+impl ToHex for Tx {
+    fn to_hex(&self) -> String {
+        hex(&self.serialize())
+    }
+}
+
+impl FromHex for Tx {
+    type Error = EncodeError;
+
+    fn from_hex(s: &str) -> Result<Self, EncodeError> {
+        deserialize(&Vec::from_hex(s)?)
     }
 }
 
 impl TxFetcher {
-    fn new() -> Self {
-        return Self {
+    pub fn new() -> Self {
+        Self {
             cache: HashMap::new(),
-        };
+        }
     }
 
-    fn get_url(testnet: bool) -> String {
-        if testnet {
-            String::from("http://testnet.programmingbitcoin.com")
-        } else {
-            String::from("http://mainnet.programmingbitcoin.com")
+    // only mainnet and testnet3 are served
+    fn get_host(network: Network) -> Result<&'static str, FetchError> {
+        match network {
+            Network::Mainnet => Ok("mainnet.programmingbitcoin.com"),
+            Network::Testnet3 => Ok("testnet.programmingbitcoin.com"),
+            Network::Testnet4 | Network::Signet | Network::Regtest => {
+                Err(FetchError::UnsupportedNetwork(network))
+            }
         }
     }
 
-    fn fetch(&self, tx_id: Vec<u8>, testnet: bool, fresh: bool) -> Tx {
-        match (fresh, self.cache.get(&tx_id)) {
-            (true, _) | (false, None) => {
-                let url = format!("{}/tx/{}.hex", TxFetcher::get_url(testnet), hex(tx_id));
-                let response = requests.get(url)
-            }
-            (false, Some(x)) => {
-                self.cache.get(&tx_id).unwrap().testnet = testnet;
-                *self.cache.get(&tx_id).unwrap()
+    // the body of a plain HTTP GET
+    fn http_get(host: &str, path: &str) -> Result<String, FetchError> {
+        let mut stream = TcpStream::connect((host, 80))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        write!(
+            stream,
+            "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            path, host
+        )?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+        let status = head.lines().next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("200") {
+            return Err(FetchError::Http(status.to_string()));
+        }
+        Ok(body.trim().to_string())
+    }
+
+    // fresh skips the cache
+    pub fn fetch(&mut self, tx_id: Txid, network: Network, fresh: bool) -> Result<Tx, FetchError> {
+        if !fresh {
+            if let Some(tx) = self.cache.get(&tx_id) {
+                let mut tx = tx.clone();
+                tx.network = network;
+                return Ok(tx);
             }
         }
+        let path = format!("/tx/{}.hex", tx_id);
+        let body = Self::http_get(Self::get_host(network)?, &path)?;
+        let mut tx = Tx::from_hex(&body)?;
+        tx.network = network;
+        if tx.hash() != tx_id {
+            return Err(FetchError::IdMismatch);
+        }
+        self.cache.insert(tx_id, tx.clone());
+        Ok(tx)
     }
 }
 
-// !TODO: 5.5章, 練習問題5 をテストに追加する。 Script についての実装ができていないので、そちらができた後にテストを追加する。
 #[cfg(test)]
 mod tests {
-    // #[test]
-    // fn hex_test() {
-    //     let b = BigInt::from(8u8);
-    // }
+    use super::*;
+    use crate::util::hex::HexError;
+
+    // from chapter 5 of Programming Bitcoin
+    const TX_HEX: &str = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+
+    const SEGWIT_TX_HEX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    #[test]
+    fn parse_test() {
+        let raw = Vec::from_hex(TX_HEX).unwrap();
+        let tx = Tx::parse(&mut raw.as_slice(), Network::Testnet3).unwrap();
+        assert_eq!(tx.version, 1);
+        assert_eq!(tx.network, Network::Testnet3);
+
+        assert_eq!(tx.tx_ins.len(), 1);
+        assert_eq!(
+            tx.tx_ins[0].prev_tx.to_string(),
+            "d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81"
+        );
+        // stored in wire order
+        assert_eq!(tx.tx_ins[0].prev_tx.as_bytes()[0], 0x81);
+        assert_eq!(tx.tx_ins[0].prev_index, 0);
+        assert_eq!(hex(&tx.tx_ins[0].script_sig[..3]), "483045");
+        assert_eq!(tx.tx_ins[0].script_sig.len(), 0x6b);
+        assert_eq!(tx.tx_ins[0].sequence, 0xfffffffe);
+
+        assert_eq!(tx.tx_outs.len(), 2);
+        assert_eq!(tx.tx_outs[0].amount, Amount::from_sat(32454049));
+        assert_eq!(
+            hex(&tx.tx_outs[0].script_pubkey),
+            "76a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac"
+        );
+        assert_eq!(tx.tx_outs[1].amount.to_string(), "0.10011545 BTC");
+        assert_eq!(tx.locktime, 410393);
+    }
+
+    #[test]
+    fn serialize_test() {
+        let raw = Vec::from_hex(TX_HEX).unwrap();
+        let tx: Tx = deserialize(&raw).unwrap();
+        assert_eq!(tx.serialize(), raw);
+        let mut bytes = vec![];
+        assert_eq!(tx.consensus_encode(&mut bytes).unwrap(), raw.len());
+        assert_eq!(
+            tx.id(),
+            "452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03"
+        );
+        assert_eq!(tx.hash(), tx.id().parse::<Txid>().unwrap());
+        assert_eq!(false, tx.is_segwit());
+        assert_eq!(tx.wtxid().to_byte_array(), tx.hash().to_byte_array());
+
+        assert_eq!(tx.to_hex(), TX_HEX);
+        assert_eq!(Tx::from_hex(TX_HEX).unwrap(), tx);
+
+        let tx_out: TxOut = deserialize(&serialize(&tx.tx_outs[1])).unwrap();
+        assert_eq!(tx_out, tx.tx_outs[1]);
+    }
+
+    #[test]
+    fn segwit_test() {
+        // BIP143's native P2WPKH example: the second input spends a P2WPKH output
+        let raw = Vec::from_hex(SEGWIT_TX_HEX).unwrap();
+        let tx: Tx = deserialize(&raw).unwrap();
+        assert_eq!(true, tx.is_segwit());
+        assert_eq!(tx.tx_ins.len(), 2);
+        assert_eq!(tx.tx_ins[0].witness.len(), 0);
+        assert_eq!(tx.tx_ins[1].witness.len(), 2);
+        assert_eq!(tx.tx_ins[1].witness[1].len(), 33);
+        assert_eq!(tx.locktime, 17);
+        assert_eq!(tx.serialize(), raw);
+        assert_eq!(tx.wtxid(), Wtxid::hash(&raw));
+        assert_eq!(
+            false,
+            tx.wtxid().to_byte_array() == tx.hash().to_byte_array()
+        );
+
+        // the txid leaves the witnesses out
+        let mut stripped = tx.clone();
+        stripped.tx_ins[1].witness.clear();
+        assert_eq!(false, stripped.is_segwit());
+        assert_eq!(stripped.hash(), tx.hash());
+        // marker and flag, an empty stack, then two items: a 71 byte signature and a key
+        assert_eq!(
+            stripped.serialize().len() + 2 + 1 + (1 + 72 + 34),
+            raw.len()
+        );
+    }
+
+    #[test]
+    fn segwit_invalid() {
+        let raw = Vec::from_hex(SEGWIT_TX_HEX).unwrap();
+        let mut bad_flag = raw.clone();
+        bad_flag[5] = 0x02;
+        assert_eq!(
+            deserialize::<Tx>(&bad_flag),
+            Err(EncodeError::InvalidSegwitFlag(2))
+        );
+
+        // a marker and flag in front of a transaction without witness data
+        let mut tx: Tx = deserialize(&raw).unwrap();
+        tx.tx_ins[1].witness.clear();
+        let mut bytes = tx.serialize();
+        bytes.splice(4..4, [0x00, 0x01]);
+        let locktime = bytes.split_off(bytes.len() - 4);
+        bytes.extend([0x00, 0x00]);
+        bytes.extend(locktime);
+        assert_eq!(deserialize::<Tx>(&bytes), Err(EncodeError::EmptyWitness));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let raw = Vec::from_hex(TX_HEX).unwrap();
+        assert_eq!(
+            deserialize::<Tx>(&raw[..raw.len() - 1]),
+            Err(EncodeError::Io(io::ErrorKind::UnexpectedEof))
+        );
+        let mut extended = raw.clone();
+        extended.push(0);
+        assert_eq!(
+            deserialize::<Tx>(&extended),
+            Err(EncodeError::TrailingBytes(1))
+        );
+        assert_eq!(
+            Tx::from_hex(&TX_HEX[1..]),
+            Err(EncodeError::Hex(HexError::OddLength(TX_HEX.len() - 1)))
+        );
+    }
+
+    #[test]
+    fn get_host_test() {
+        assert_eq!(
+            TxFetcher::get_host(Network::Mainnet),
+            Ok("mainnet.programmingbitcoin.com")
+        );
+        assert_eq!(
+            TxFetcher::get_host(Network::Testnet3),
+            Ok("testnet.programmingbitcoin.com")
+        );
+        for network in [Network::Testnet4, Network::Signet, Network::Regtest] {
+            assert_eq!(
+                TxFetcher::get_host(network),
+                Err(FetchError::UnsupportedNetwork(network))
+            );
+        }
+        // rejected before any connection is made
+        let txid = Txid::all_zeros();
+        assert_eq!(
+            TxFetcher::new().fetch(txid, Network::Regtest, true),
+            Err(FetchError::UnsupportedNetwork(Network::Regtest))
+        );
+        assert_eq!(TxFetcher::new().cache.len(), 0);
+    }
 }
@@ -1,6 +1,13 @@
 pub mod base58;
+pub mod bech32;
+pub mod bigint;
+pub mod encode;
 pub mod hash160;
 pub mod hash256;
+pub mod hash_engine;
+pub mod hash_types;
 pub mod hex;
-pub mod reader;
+pub mod hmac_sha512;
+pub mod pbkdf2;
+pub mod tagged_hash;
 pub mod varint;
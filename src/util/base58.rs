@@ -1,13 +1,52 @@
 use super::hash256::hash256;
 use bs58;
+
+const ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Base58Error {
+    // the offending character and its position in the string
+    InvalidCharacter(char, usize),
+    InvalidChecksum,
+    // holds the decoded length, too short for the checksum (and version byte)
+    TooShort(usize),
+}
+
 pub fn encode_base58_checksum(byte: &[u8]) -> String {
     let mut bytes = byte.to_vec();
-    bytes.extend(&hash256(&byte)[..4]);
+    bytes.extend(&hash256(byte)[..4]);
     bs58::encode(bytes).into_string()
 }
 
+// the payload of encode_base58_checksum, with the checksum verified and removed
+pub fn decode_base58_checksum(s: &str) -> Result<Vec<u8>, Base58Error> {
+    if let Some((position, c)) = s.chars().enumerate().find(|(_, c)| !ALPHABET.contains(*c)) {
+        return Err(Base58Error::InvalidCharacter(c, position));
+    }
+    let mut bytes = bs58::decode(s).into_vec().unwrap();
+    if bytes.len() < 4 {
+        return Err(Base58Error::TooShort(bytes.len()));
+    }
+    let checksum = bytes.split_off(bytes.len() - 4);
+    if hash256(&bytes)[..4] != checksum {
+        return Err(Base58Error::InvalidChecksum);
+    }
+    Ok(bytes)
+}
+
+// splits off the version byte that starts addresses (0x00, 0x05, 0x6f, ...) and WIF keys
+pub fn decode_base58_check_version(s: &str) -> Result<(u8, Vec<u8>), Base58Error> {
+    let mut bytes = decode_base58_checksum(s)?;
+    if bytes.is_empty() {
+        return Err(Base58Error::TooShort(4));
+    }
+    let payload = bytes.split_off(1);
+    Ok((bytes[0], payload))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
     use bs58::encode;
     use num_bigint::BigInt;
 
@@ -43,4 +82,55 @@ mod tests {
             "EQJsjkd6JaGwxrjEhfeqPenqHwrBmPQZjJGNSCHBkcF7"
         );
     }
+
+    #[test]
+    fn decode_checksum_test() {
+        let payload = b"programming bitcoin".to_vec();
+        let s = encode_base58_checksum(&payload);
+        assert_eq!(decode_base58_checksum(&s).unwrap(), payload);
+        assert_eq!(
+            decode_base58_checksum(&encode_base58_checksum(&[])).unwrap(),
+            vec![]
+        );
+
+        let (version, hash) =
+            decode_base58_check_version("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH").unwrap();
+        assert_eq!(version, 0x00);
+        assert_eq!(
+            hash,
+            BigInt::parse_bytes(b"751e76e8199196d454941c45d1b3a323f1433bd6", 16)
+                .unwrap()
+                .to_bytes_be()
+                .1
+        );
+        let (version, _) =
+            decode_base58_check_version("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy").unwrap();
+        assert_eq!(version, 0x05);
+    }
+
+    #[test]
+    fn decode_checksum_invalid() {
+        assert_eq!(
+            decode_base58_checksum("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMI"),
+            Err(Base58Error::InvalidCharacter('I', 33))
+        );
+        assert_eq!(
+            decode_base58_checksum("1BgG0"),
+            Err(Base58Error::InvalidCharacter('0', 4))
+        );
+        assert_eq!(
+            decode_base58_checksum("é1BgG"),
+            Err(Base58Error::InvalidCharacter('é', 0))
+        );
+        assert_eq!(
+            decode_base58_checksum("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMJ"),
+            Err(Base58Error::InvalidChecksum)
+        );
+        assert_eq!(decode_base58_checksum("111"), Err(Base58Error::TooShort(3)));
+        assert_eq!(decode_base58_checksum(""), Err(Base58Error::TooShort(0)));
+        assert_eq!(
+            decode_base58_check_version(&encode_base58_checksum(&[])),
+            Err(Base58Error::TooShort(4))
+        );
+    }
 }
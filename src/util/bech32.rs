@@ -0,0 +1,491 @@
+// Bech32 (BIP173) and Bech32m (BIP350) strings, and the segwit addresses built on them:
+// hrp || '1' || data || 6 character checksum, with 5 bits per data character.
+// Witness version 0 addresses use Bech32, versions 1 to 16 use Bech32m.
+use crate::network::Network;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+const MAX_LENGTH: usize = 90;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Variant {
+    Bech32,
+    Bech32m,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Bech32Error {
+    // the offending character and its position in the string
+    InvalidCharacter(char, usize),
+    // a data value above 31 and its position in the data
+    InvalidValue(u8, usize),
+    MixedCase,
+    MissingSeparator,
+    EmptyHrp,
+    // longer than 90 characters
+    TooLong(usize),
+    // fewer than 6 characters after the separator
+    TooShortChecksum,
+    // holds the position of the wrong character when changing a single one fixes the checksum
+    InvalidChecksum(Option<usize>),
+    // leftover bits when converting between 5 and 8 bit groups
+    InvalidPadding,
+    // not one of bc, tb or bcrt
+    UnknownHrp(String),
+    InvalidWitnessVersion(u8),
+    InvalidProgramLength(usize),
+    // version 0 must use Bech32, later versions Bech32m
+    InvalidVariant(Variant),
+}
+
+impl Variant {
+    fn constant(self) -> u32 {
+        match self {
+            Variant::Bech32 => 1,
+            Variant::Bech32m => 0x2bc830a3,
+        }
+    }
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    let mut chk: u32 = 1;
+    for value in values {
+        let top = chk >> 25;
+        chk = (chk & 0x1ffffff) << 5 ^ *value as u32;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 31));
+    values
+}
+
+// the variant whose checksum data (including the 6 checksum characters) ends with
+fn checksum_variant(hrp: &str, data: &[u8]) -> Option<Variant> {
+    let mut values = hrp_expand(hrp);
+    values.extend(data);
+    match polymod(&values) {
+        1 => Some(Variant::Bech32),
+        0x2bc830a3 => Some(Variant::Bech32m),
+        _ => None,
+    }
+}
+
+fn create_checksum(hrp: &str, data: &[u8], variant: Variant) -> Vec<u8> {
+    let mut values = hrp_expand(hrp);
+    values.extend(data);
+    values.extend([0; 6]);
+    let polymod = polymod(&values) ^ variant.constant();
+    (0..6)
+        .map(|i| (polymod >> (5 * (5 - i)) & 31) as u8)
+        .collect()
+}
+
+// Finds a single substituted character by trying every replacement; the checksum guarantees
+// that at most one of them works.
+fn locate_error(hrp: &str, data: &[u8]) -> Option<usize> {
+    let mut data = data.to_vec();
+    for i in 0..data.len() {
+        let original = data[i];
+        for value in 0..32 {
+            data[i] = value;
+            if value != original && checksum_variant(hrp, &data).is_some() {
+                return Some(hrp.len() + 1 + i);
+            }
+        }
+        data[i] = original;
+    }
+    None
+}
+
+// data holds 5 bit values
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> Result<String, Bech32Error> {
+    if hrp.is_empty() {
+        return Err(Bech32Error::EmptyHrp);
+    }
+    if let Some((position, c)) = hrp
+        .chars()
+        .enumerate()
+        .find(|(_, c)| !matches!(c, '!'..='~'))
+    {
+        return Err(Bech32Error::InvalidCharacter(c, position));
+    }
+    if let Some((position, value)) = data.iter().enumerate().find(|(_, value)| **value > 31) {
+        return Err(Bech32Error::InvalidValue(*value, position));
+    }
+    let length = hrp.len() + 1 + data.len() + 6;
+    if length > MAX_LENGTH {
+        return Err(Bech32Error::TooLong(length));
+    }
+    let hrp = hrp.to_lowercase();
+    let mut s = hrp.clone();
+    s.push('1');
+    for value in data
+        .iter()
+        .copied()
+        .chain(create_checksum(&hrp, data, variant))
+    {
+        s.push(CHARSET[value as usize] as char);
+    }
+    Ok(s)
+}
+
+// returns the lowercase hrp, the 5 bit data without the checksum, and the checksum variant
+pub fn decode(s: &str) -> Result<(String, Vec<u8>, Variant), Bech32Error> {
+    if let Some((position, c)) = s.chars().enumerate().find(|(_, c)| !matches!(c, '!'..='~')) {
+        return Err(Bech32Error::InvalidCharacter(c, position));
+    }
+    if s.len() > MAX_LENGTH {
+        return Err(Bech32Error::TooLong(s.len()));
+    }
+    if s.chars().any(|c| c.is_ascii_lowercase()) && s.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(Bech32Error::MixedCase);
+    }
+    let s = s.to_lowercase();
+    let separator = s.rfind('1').ok_or(Bech32Error::MissingSeparator)?;
+    if separator == 0 {
+        return Err(Bech32Error::EmptyHrp);
+    }
+    let (hrp, rest) = (&s[..separator], &s[separator + 1..]);
+    if rest.len() < 6 {
+        return Err(Bech32Error::TooShortChecksum);
+    }
+    let mut data = vec![];
+    for (i, c) in rest.chars().enumerate() {
+        match CHARSET.iter().position(|value| *value as char == c) {
+            Some(value) => data.push(value as u8),
+            None => return Err(Bech32Error::InvalidCharacter(c, separator + 1 + i)),
+        }
+    }
+    let variant = checksum_variant(hrp, &data)
+        .ok_or_else(|| Bech32Error::InvalidChecksum(locate_error(hrp, &data)))?;
+    data.truncate(data.len() - 6);
+    Ok((hrp.to_string(), data, variant))
+}
+
+// Regroups bits, e.g. bytes into 5 bit values and back. Without padding, leftover bits must
+// be fewer than `from` and all zero.
+pub fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, Bech32Error> {
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let mut result = vec![];
+    let max = (1 << to) - 1;
+    // keeps acc to the bits that can still be pending
+    let mask = (1 << (from + to - 1)) - 1;
+    for value in data {
+        if (*value as u32) >> from != 0 {
+            return Err(Bech32Error::InvalidPadding);
+        }
+        acc = (acc << from | *value as u32) & mask;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push((acc >> bits & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push((acc << (to - bits) & max) as u8);
+        }
+    } else if bits >= from || acc << (to - bits) & max != 0 {
+        return Err(Bech32Error::InvalidPadding);
+    }
+    Ok(result)
+}
+
+fn check_program(version: u8, program: &[u8]) -> Result<(), Bech32Error> {
+    if version > 16 {
+        return Err(Bech32Error::InvalidWitnessVersion(version));
+    }
+    if !(2..=40).contains(&program.len()) || version == 0 && ![20, 32].contains(&program.len()) {
+        return Err(Bech32Error::InvalidProgramLength(program.len()));
+    }
+    Ok(())
+}
+
+pub fn encode_segwit_address(
+    hrp: &str,
+    version: u8,
+    program: &[u8],
+) -> Result<String, Bech32Error> {
+    if Network::from_bech32_hrp(hrp).is_none() {
+        return Err(Bech32Error::UnknownHrp(hrp.to_string()));
+    }
+    check_program(version, program)?;
+    let variant = if version == 0 {
+        Variant::Bech32
+    } else {
+        Variant::Bech32m
+    };
+    let mut data = vec![version];
+    data.extend(convert_bits(program, 8, 5, true)?);
+    encode(hrp, &data, variant)
+}
+
+// returns (hrp, witness version, witness program)
+pub fn decode_segwit_address(s: &str) -> Result<(String, u8, Vec<u8>), Bech32Error> {
+    let (hrp, data, variant) = decode(s)?;
+    if Network::from_bech32_hrp(&hrp).is_none() {
+        return Err(Bech32Error::UnknownHrp(hrp));
+    }
+    let version = *data.first().ok_or(Bech32Error::InvalidProgramLength(0))?;
+    let program = convert_bits(&data[1..], 5, 8, false)?;
+    check_program(version, &program)?;
+    match (version, variant) {
+        (0, Variant::Bech32) | (1..=16, Variant::Bech32m) => Ok((hrp, version, program)),
+        _ => Err(Bech32Error::InvalidVariant(variant)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex::hex;
+
+    #[test]
+    fn valid_strings() {
+        for s in [
+            "A12UEL5L",
+            "a12uel5l",
+            "an83characterlonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1tt5tgs",
+            "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+            "11qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqc8247j",
+            "split1checkupstagehandshakeupstreamerranterredcaperred2y9e3w",
+            "?1ezyfcl",
+        ] {
+            let (hrp, data, variant) = decode(s).unwrap();
+            assert_eq!(variant, Variant::Bech32);
+            assert_eq!(encode(&hrp, &data, variant).unwrap(), s.to_lowercase());
+        }
+        for s in [
+            "A1LQFN3A",
+            "a1lqfn3a",
+            "an83characterlonghumanreadablepartthatcontainsthetheexcludedcharactersbioandnumber11sg7hg6",
+            "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+            "11llllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllludsr8",
+            "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+            "?1v759aa",
+        ] {
+            let (hrp, data, variant) = decode(s).unwrap();
+            assert_eq!(variant, Variant::Bech32m);
+            assert_eq!(encode(&hrp, &data, variant).unwrap(), s.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn invalid_strings() {
+        assert_eq!(
+            decode("\u{20}1nwldj5"),
+            Err(Bech32Error::InvalidCharacter(' ', 0))
+        );
+        assert_eq!(
+            decode("\u{7f}1axkwrx"),
+            Err(Bech32Error::InvalidCharacter('\u{7f}', 0))
+        );
+        assert_eq!(
+            decode("\u{80}1eym55h"),
+            Err(Bech32Error::InvalidCharacter('\u{80}', 0))
+        );
+        assert_eq!(
+            decode("an84characterslonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1569pvx"),
+            Err(Bech32Error::TooLong(91))
+        );
+        assert_eq!(decode("pzry9x0s0muk"), Err(Bech32Error::MissingSeparator));
+        assert_eq!(decode("1pzry9x0s0muk"), Err(Bech32Error::EmptyHrp));
+        assert_eq!(
+            decode("x1b4n0q5v"),
+            Err(Bech32Error::InvalidCharacter('b', 2))
+        );
+        assert_eq!(decode("li1dgmt3"), Err(Bech32Error::TooShortChecksum));
+        assert_eq!(
+            decode("de1lg7wt\u{ff}"),
+            Err(Bech32Error::InvalidCharacter('\u{ff}', 8))
+        );
+        assert_eq!(
+            decode("mm1crxm3i"),
+            Err(Bech32Error::InvalidCharacter('i', 8))
+        );
+        // checksum calculated with the uppercase hrp
+        assert_eq!(
+            true,
+            matches!(decode("A1G7SGD8"), Err(Bech32Error::InvalidChecksum(_)))
+        );
+        assert_eq!(
+            true,
+            matches!(decode("M1VUXWEZ"), Err(Bech32Error::InvalidChecksum(_)))
+        );
+        assert_eq!(decode("10a06t8"), Err(Bech32Error::EmptyHrp));
+        assert_eq!(decode("16plkw9"), Err(Bech32Error::EmptyHrp));
+
+        assert_eq!(
+            encode("a", &[0, 31, 32], Variant::Bech32),
+            Err(Bech32Error::InvalidValue(32, 2))
+        );
+    }
+
+    #[test]
+    fn locate_error_test() {
+        let s = "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw";
+        let mut typo = s.to_string();
+        typo.replace_range(20..21, "q");
+        assert_eq!(decode(&typo), Err(Bech32Error::InvalidChecksum(Some(20))));
+        let mut typo = s.to_string();
+        typo.replace_range(43..44, "z");
+        assert_eq!(decode(&typo), Err(Bech32Error::InvalidChecksum(Some(43))));
+    }
+
+    #[test]
+    fn valid_segwit_addresses() {
+        let vectors = [
+            (
+                "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+                "bc",
+                0,
+                "751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            (
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+                "tb",
+                0,
+                "1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            ),
+            (
+                "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+                "bc",
+                1,
+                "751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            ("BC1SW50QGDZ25J", "bc", 16, "751e"),
+            (
+                "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs",
+                "bc",
+                2,
+                "751e76e8199196d454941c45d1b3a323",
+            ),
+            (
+                "tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy",
+                "tb",
+                0,
+                "000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+            (
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+                "tb",
+                1,
+                "000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                "bc",
+                1,
+                "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            ),
+        ];
+        for (address, hrp, version, program) in vectors {
+            let decoded = decode_segwit_address(address).unwrap();
+            assert_eq!((decoded.0.as_str(), decoded.1), (hrp, version));
+            assert_eq!(hex(&decoded.2), program);
+            assert_eq!(
+                encode_segwit_address(hrp, version, &decoded.2).unwrap(),
+                address.to_lowercase()
+            );
+        }
+        let regtest = encode_segwit_address(Network::Regtest.bech32_hrp(), 0, &[0x75; 20]).unwrap();
+        assert_eq!(&regtest[..5], "bcrt1");
+        assert_eq!(decode_segwit_address(&regtest).unwrap().2, vec![0x75; 20]);
+    }
+
+    #[test]
+    fn invalid_segwit_addresses() {
+        let decode = decode_segwit_address;
+        assert_eq!(
+            decode("tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut"),
+            Err(Bech32Error::UnknownHrp("tc".to_string()))
+        );
+        // Bech32m for version 0 and Bech32 for later versions
+        assert_eq!(
+            decode("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd"),
+            Err(Bech32Error::InvalidVariant(Variant::Bech32))
+        );
+        assert_eq!(
+            decode("tb1z0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqglt7rf"),
+            Err(Bech32Error::InvalidVariant(Variant::Bech32))
+        );
+        assert_eq!(
+            decode("BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL"),
+            Err(Bech32Error::InvalidVariant(Variant::Bech32))
+        );
+        assert_eq!(
+            decode("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh"),
+            Err(Bech32Error::InvalidVariant(Variant::Bech32m))
+        );
+        assert_eq!(
+            decode("tb1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq24jc47"),
+            Err(Bech32Error::InvalidVariant(Variant::Bech32m))
+        );
+        assert_eq!(
+            decode("bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4"),
+            Err(Bech32Error::InvalidCharacter('o', 59))
+        );
+        assert_eq!(
+            decode("BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R"),
+            Err(Bech32Error::InvalidWitnessVersion(17))
+        );
+        assert_eq!(
+            decode("bc1pw5dgrnzv"),
+            Err(Bech32Error::InvalidProgramLength(1))
+        );
+        assert_eq!(
+            decode("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav"),
+            Err(Bech32Error::InvalidProgramLength(41))
+        );
+        assert_eq!(
+            decode("BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P"),
+            Err(Bech32Error::InvalidProgramLength(16))
+        );
+        assert_eq!(
+            decode("tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq"),
+            Err(Bech32Error::MixedCase)
+        );
+        assert_eq!(
+            decode("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf"),
+            Err(Bech32Error::InvalidPadding)
+        );
+        assert_eq!(
+            decode("tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j"),
+            Err(Bech32Error::InvalidPadding)
+        );
+        assert_eq!(
+            decode("bc1gmk9yu"),
+            Err(Bech32Error::InvalidProgramLength(0))
+        );
+        assert_eq!(
+            true,
+            matches!(
+                decode("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5"),
+                Err(Bech32Error::InvalidChecksum(_))
+            )
+        );
+
+        assert_eq!(
+            encode_segwit_address("bc", 17, &[0; 20]),
+            Err(Bech32Error::InvalidWitnessVersion(17))
+        );
+        assert_eq!(
+            encode_segwit_address("bc", 0, &[0; 21]),
+            Err(Bech32Error::InvalidProgramLength(21))
+        );
+        assert_eq!(
+            encode_segwit_address("ltc", 0, &[0; 20]),
+            Err(Bech32Error::UnknownHrp("ltc".to_string()))
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::One;
+
+// order n of the secp256k1 group
+pub fn order() -> BigInt {
+    let bytes_n = b"fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141";
+    BigInt::parse_bytes(bytes_n, 16).unwrap()
+}
+
+// uniformly random in [1, n)
+pub fn random_scalar() -> BigInt {
+    rand::thread_rng().gen_bigint_range(&BigInt::one(), &order())
+}
+
+// k^-1 mod n; k must not be a multiple of n
+pub fn inverse(k: &BigInt) -> BigInt {
+    let n = order();
+    k.modpow(&(&n - BigInt::from(2u8)), &n)
+}
+
+// big-endian bytes of num, zero-padded on the left up to 32 bytes
+pub fn to_32_bytes(num: &BigInt) -> Vec<u8> {
+    let (_, bytes) = num.to_bytes_be();
+    let mut result = vec![0x0; 32 - bytes.len()];
+    result.extend(bytes);
+    result
+}
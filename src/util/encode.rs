@@ -0,0 +1,239 @@
+// Consensus serialization: the byte format of transactions and blocks on the wire and on disk.
+// Integers are little-endian and sequences are prefixed with their length as a VarInt.
+use super::hex::HexError;
+use super::varint::{read_compact_size, write_compact_size};
+use std::io::{self, Read, Write};
+
+// more elements than any valid block could hold; guards against huge length prefixes
+const MAX_VEC_SIZE: u64 = 4_000_000;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EncodeError {
+    // includes running out of input (UnexpectedEof)
+    Io(io::ErrorKind),
+    // holds the number of bytes deserialize left unread
+    TrailingBytes(usize),
+    OversizedVector(u64),
+    // a CompactSize encoded in more bytes than needed
+    NonCanonicalCompactSize,
+    // above varint::MAX_SIZE
+    OversizedCompactSize(u64),
+    // when decoding from a hex string
+    Hex(HexError),
+    // a BIP144 marker followed by a flag other than 1
+    InvalidSegwitFlag(u8),
+    // a BIP144 marker on a transaction whose witnesses are all empty
+    EmptyWitness,
+}
+
+impl From<io::Error> for EncodeError {
+    fn from(e: io::Error) -> Self {
+        EncodeError::Io(e.kind())
+    }
+}
+
+impl From<HexError> for EncodeError {
+    fn from(e: HexError) -> Self {
+        EncodeError::Hex(e)
+    }
+}
+
+pub trait Encodable {
+    // returns the number of bytes written
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize>;
+}
+
+pub trait Decodable: Sized {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError>;
+}
+
+pub fn serialize<T: Encodable + ?Sized>(value: &T) -> Vec<u8> {
+    let mut bytes = vec![];
+    // writing to a Vec can't fail
+    value.consensus_encode(&mut bytes).unwrap();
+    bytes
+}
+
+// the whole input must be used
+pub fn deserialize<T: Decodable>(bytes: &[u8]) -> Result<T, EncodeError> {
+    let mut reader = bytes;
+    let value = T::consensus_decode(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(EncodeError::TrailingBytes(reader.len()));
+    }
+    Ok(value)
+}
+
+// CompactSize unsigned integer: 1, 3, 5 or 9 bytes, see util::varint
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct VarInt(pub u64);
+
+impl Encodable for VarInt {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let bytes = write_compact_size(self.0);
+        writer.write_all(&bytes)?;
+        Ok(bytes.len())
+    }
+}
+
+impl Decodable for VarInt {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError> {
+        Ok(VarInt(read_compact_size(reader)?))
+    }
+}
+
+macro_rules! impl_int_encodable {
+    ($($ty:ty),*) => {
+        $(
+            impl Encodable for $ty {
+                fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+                    writer.write_all(&self.to_le_bytes())?;
+                    Ok(std::mem::size_of::<$ty>())
+                }
+            }
+
+            impl Decodable for $ty {
+                fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError> {
+                    let mut bytes = [0u8; std::mem::size_of::<$ty>()];
+                    reader.read_exact(&mut bytes)?;
+                    Ok(<$ty>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_int_encodable!(u8, u16, u32, u64, i32, i64);
+
+// fixed size, so no length prefix
+impl<const N: usize> Encodable for [u8; N] {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        writer.write_all(self)?;
+        Ok(N)
+    }
+}
+
+impl<const N: usize> Decodable for [u8; N] {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError> {
+        let mut bytes = [0u8; N];
+        reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl<T: Encodable> Encodable for [T] {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let mut len = VarInt(self.len() as u64).consensus_encode(writer)?;
+        for item in self {
+            len += item.consensus_encode(writer)?;
+        }
+        Ok(len)
+    }
+}
+
+impl<T: Encodable> Encodable for Vec<T> {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        self.as_slice().consensus_encode(writer)
+    }
+}
+
+impl<T: Decodable> Decodable for Vec<T> {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError> {
+        let VarInt(len) = VarInt::consensus_decode(reader)?;
+        if len > MAX_VEC_SIZE {
+            return Err(EncodeError::OversizedVector(len));
+        }
+        // grows as items arrive rather than trusting len for the allocation
+        let mut items = vec![];
+        for _ in 0..len {
+            items.push(T::consensus_decode(reader)?);
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex::hex;
+
+    #[test]
+    fn integers_test() {
+        assert_eq!(serialize(&1u32), vec![1, 0, 0, 0]);
+        assert_eq!(serialize(&-2i32), vec![0xfe, 0xff, 0xff, 0xff]);
+        assert_eq!(deserialize::<u16>(&[0x34, 0x12]).unwrap(), 0x1234);
+        assert_eq!(
+            deserialize::<u64>(&[1, 2, 3]),
+            Err(EncodeError::Io(io::ErrorKind::UnexpectedEof))
+        );
+        assert_eq!(
+            deserialize::<u16>(&[1, 2, 3]),
+            Err(EncodeError::TrailingBytes(1))
+        );
+
+        let mut bytes = vec![];
+        assert_eq!(0xdeadbeefu32.consensus_encode(&mut bytes).unwrap(), 4);
+        assert_eq!(5u64.consensus_encode(&mut bytes).unwrap(), 8);
+        assert_eq!(bytes.len(), 12);
+    }
+
+    #[test]
+    fn varint_test() {
+        for (value, encoded) in [
+            (0, "00"),
+            (0xfc, "fc"),
+            (0xfd, "fdfd00"),
+            (0xffff, "fdffff"),
+            (0x10000, "fe00000100"),
+            (0x200_0000, "fe00000002"),
+        ] {
+            assert_eq!(hex(&serialize(&VarInt(value))), encoded);
+            assert_eq!(
+                deserialize::<VarInt>(&serialize(&VarInt(value))).unwrap(),
+                VarInt(value)
+            );
+        }
+        assert_eq!(
+            hex(&serialize(&VarInt(0x1_0000_0000))),
+            "ff0000000001000000"
+        );
+        // larger than any length bitcoind reads
+        assert_eq!(
+            deserialize::<VarInt>(&serialize(&VarInt(0x1_0000_0000))),
+            Err(EncodeError::OversizedCompactSize(0x1_0000_0000))
+        );
+        assert_eq!(
+            deserialize::<VarInt>(&[0xfd, 0x01, 0x00]),
+            Err(EncodeError::NonCanonicalCompactSize)
+        );
+    }
+
+    #[test]
+    fn vec_and_array_test() {
+        let bytes = serialize(&vec![1u8, 2, 3]);
+        assert_eq!(bytes, vec![3, 1, 2, 3]);
+        assert_eq!(deserialize::<Vec<u8>>(&bytes).unwrap(), vec![1, 2, 3]);
+
+        let words = vec![1u32, 2];
+        assert_eq!(serialize(&words).len(), 9);
+        assert_eq!(deserialize::<Vec<u32>>(&serialize(&words)).unwrap(), words);
+
+        // arrays have no length prefix
+        assert_eq!(serialize(&[7u8; 4]), vec![7; 4]);
+        assert_eq!(deserialize::<[u8; 2]>(&[1, 2]).unwrap(), [1, 2]);
+
+        // claims 3 elements, has 2
+        assert_eq!(
+            deserialize::<Vec<u8>>(&[3, 1, 2]),
+            Err(EncodeError::Io(io::ErrorKind::UnexpectedEof))
+        );
+        assert_eq!(
+            deserialize::<Vec<u8>>(&[0xfe, 0x00, 0x00, 0x00, 0x02]),
+            Err(EncodeError::OversizedVector(0x200_0000))
+        );
+        assert_eq!(
+            deserialize::<Vec<u8>>(&[0xfe, 0xff, 0xff, 0xff, 0xff]),
+            Err(EncodeError::OversizedCompactSize(0xffff_ffff))
+        );
+    }
+}
@@ -0,0 +1,148 @@
+// Incremental hashing for payloads too large, or too scattered, to collect into one buffer.
+// All three hashes start with a streamed SHA256 and only differ in how it is finished.
+use bitcoin_hashes::{ripemd160, sha256, Hash, HashEngine as _};
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Algorithm {
+    Sha256,
+    // sha256(sha256(data))
+    Hash256,
+    // ripemd160(sha256(data))
+    Hash160,
+}
+
+#[derive(Clone)]
+pub struct HashEngine {
+    algorithm: Algorithm,
+    engine: sha256::HashEngine,
+}
+
+impl HashEngine {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            engine: sha256::Hash::engine(),
+        }
+    }
+
+    pub fn sha256() -> Self {
+        Self::new(Algorithm::Sha256)
+    }
+
+    pub fn hash256() -> Self {
+        Self::new(Algorithm::Hash256)
+    }
+
+    pub fn hash160() -> Self {
+        Self::new(Algorithm::Hash160)
+    }
+
+    // resumes a SHA256 from its state after length bytes; None unless length is a multiple of
+    // 64, as a midstate only exists at block boundaries
+    pub fn from_midstate(midstate: [u8; 32], length: usize) -> Option<Self> {
+        if !length.is_multiple_of(64) {
+            return None;
+        }
+        Some(Self {
+            algorithm: Algorithm::Sha256,
+            engine: sha256::HashEngine::from_midstate(sha256::Midstate(midstate), length),
+        })
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn input(&mut self, data: &[u8]) {
+        self.engine.input(data);
+    }
+
+    // bytes fed in so far
+    pub fn len(&self) -> usize {
+        self.engine.n_bytes_hashed()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        let sha256 = sha256::Hash::from_engine(self.engine);
+        match self.algorithm {
+            Algorithm::Sha256 => sha256.to_vec(),
+            Algorithm::Hash256 => sha256::Hash::hash(&sha256).to_vec(),
+            Algorithm::Hash160 => ripemd160::Hash::hash(&sha256).to_vec(),
+        }
+    }
+}
+
+impl Write for HashEngine {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hash160::hash160;
+    use crate::util::hash256::hash256;
+    use crate::util::hex::hex;
+
+    #[test]
+    fn streaming_test() {
+        let data = vec![0x61u8; 1000];
+        for (algorithm, expected) in [
+            (Algorithm::Sha256, sha256::Hash::hash(&data).to_vec()),
+            (Algorithm::Hash256, hash256(&data)),
+            (Algorithm::Hash160, hash160(&data)),
+        ] {
+            let mut engine = HashEngine::new(algorithm);
+            // uneven pieces that straddle the 64 byte blocks
+            for chunk in data.chunks(37) {
+                engine.input(chunk);
+            }
+            assert_eq!(engine.len(), 1000);
+            assert_eq!(engine.finalize(), expected);
+        }
+    }
+
+    #[test]
+    fn write_test() {
+        let mut engine = HashEngine::sha256();
+        assert_eq!(true, engine.is_empty());
+        write!(engine, "a").unwrap();
+        engine.write_all(b"bc").unwrap();
+        assert_eq!(
+            hex(&engine.finalize()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let mut engine = HashEngine::hash256();
+        io::copy(&mut &b"hello"[..], &mut engine).unwrap();
+        assert_eq!(
+            hex(&engine.finalize()),
+            "9595c9df90075148eb06860365df33584b75bff782a510c6cd4883a419833d50"
+        );
+    }
+
+    #[test]
+    fn midstate_test() {
+        let mut engine = HashEngine::sha256();
+        engine.input(&[7; 64]);
+        let midstate = engine.engine.midstate().into_inner();
+
+        assert_eq!(true, HashEngine::from_midstate(midstate, 63).is_none());
+        let mut resumed = HashEngine::from_midstate(midstate, 64).unwrap();
+        assert_eq!(resumed.len(), 64);
+        engine.input(b"tail");
+        resumed.input(b"tail");
+        assert_eq!(resumed.finalize(), engine.finalize());
+    }
+}
@@ -0,0 +1,174 @@
+// Fixed-size hash newtypes. Bytes are kept in the order hash functions produce them (which is
+// also the wire order); double-SHA256 hashes are displayed and parsed byte-reversed, the way
+// bitcoind and block explorers show txids and block hashes.
+use super::encode::{Decodable, Encodable, EncodeError};
+use super::hash160::hash160;
+use super::hash256::hash256;
+use super::hex::{hex, FromHex, HexError, ToHex};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+macro_rules! hash_newtype {
+    ($name:ident, $len:expr, $hash:ident, $reversed:expr) => {
+        #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        pub struct $name([u8; $len]);
+
+        impl $name {
+            pub const LEN: usize = $len;
+
+            pub fn hash(data: &[u8]) -> Self {
+                Self($hash(data).try_into().unwrap())
+            }
+
+            // bytes in internal order, not the displayed one
+            pub fn from_byte_array(bytes: [u8; $len]) -> Self {
+                Self(bytes)
+            }
+
+            pub fn to_byte_array(self) -> [u8; $len] {
+                self.0
+            }
+
+            pub fn as_bytes(&self) -> &[u8; $len] {
+                &self.0
+            }
+
+            pub fn all_zeros() -> Self {
+                Self([0; $len])
+            }
+        }
+
+        // in display order, like Display and FromStr
+        impl ToHex for $name {
+            fn to_hex(&self) -> String {
+                let mut bytes = self.0;
+                if $reversed {
+                    bytes.reverse();
+                }
+                hex(&bytes)
+            }
+        }
+
+        impl FromHex for $name {
+            type Error = HexError;
+
+            fn from_hex(s: &str) -> Result<Self, HexError> {
+                let mut bytes = <[u8; $len]>::from_hex(s)?;
+                if $reversed {
+                    bytes.reverse();
+                }
+                Ok(Self(bytes))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.to_hex())
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = HexError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::from_hex(s)
+            }
+        }
+
+        impl Encodable for $name {
+            fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+                self.0.consensus_encode(writer)
+            }
+        }
+
+        impl Decodable for $name {
+            fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, EncodeError> {
+                Ok(Self(<[u8; $len]>::consensus_decode(reader)?))
+            }
+        }
+    };
+}
+
+hash_newtype!(Hash256, 32, hash256, true);
+hash_newtype!(Txid, 32, hash256, true);
+// hash256 of a transaction serialized with its witnesses (BIP141); equals the txid when it
+// has none
+hash_newtype!(Wtxid, 32, hash256, true);
+hash_newtype!(BlockHash, 32, hash256, true);
+// shown in script order, as in addresses and scripts
+hash_newtype!(Hash160, 20, hash160, false);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::encode::{deserialize, serialize};
+    use std::collections::HashSet;
+
+    #[test]
+    fn display_test() {
+        // double-SHA256 of the empty string
+        let hash = Hash256::hash(b"");
+        assert_eq!(
+            hex(hash.as_bytes()),
+            "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456"
+        );
+        assert_eq!(
+            hash.to_string(),
+            "56944c5d3f98413ef45cf54545538103cc9f298e0575820ad3591376e2e0f65d"
+        );
+        assert_eq!(hash.to_string().parse::<Hash256>().unwrap(), hash);
+
+        let hash = Hash160::hash(b"");
+        assert_eq!(hash.to_string(), "b472a266d0bd89c13706a4132ccfb16f7c3b9fcb");
+        assert_eq!(hash.to_string().parse::<Hash160>().unwrap(), hash);
+        assert_eq!(Hash160::from_byte_array(hash.to_byte_array()), hash);
+    }
+
+    #[test]
+    fn from_str_test() {
+        // mainnet genesis block
+        let s = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        let hash: BlockHash = s.parse().unwrap();
+        assert_eq!(hash.as_bytes()[0], 0x6f);
+        assert_eq!(hash.as_bytes()[31], 0x00);
+        assert_eq!(hash.to_string(), s);
+        assert_eq!(s.to_uppercase().parse::<BlockHash>().unwrap(), hash);
+
+        assert_eq!(BlockHash::from_hex(s).unwrap(), hash);
+        assert_eq!(hash.to_hex(), s);
+
+        assert_eq!(
+            s[2..].parse::<BlockHash>(),
+            Err(HexError::InvalidLength(62))
+        );
+        assert_eq!(
+            s.replace("e2", "g2").parse::<BlockHash>(),
+            Err(HexError::InvalidCharacter('g', 60))
+        );
+        assert_eq!(s[1..].parse::<BlockHash>(), Err(HexError::OddLength(63)));
+        assert_eq!("".parse::<Hash160>(), Err(HexError::InvalidLength(0)));
+    }
+
+    #[test]
+    fn encode_test() {
+        let txid = Txid::hash(b"abc");
+        let bytes = serialize(&txid);
+        assert_eq!(bytes, txid.as_bytes());
+        assert_eq!(deserialize::<Txid>(&bytes).unwrap(), txid);
+        assert_eq!(
+            deserialize::<Hash160>(&bytes),
+            Err(EncodeError::TrailingBytes(12))
+        );
+    }
+
+    #[test]
+    fn ordering_test() {
+        let a = Txid::from_byte_array([1; 32]);
+        let b = Txid::from_byte_array([2; 32]);
+        assert_eq!(true, a < b);
+        assert_eq!(true, Txid::all_zeros() < a);
+        let set: HashSet<Txid> = [a, b, a].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+}
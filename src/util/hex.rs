@@ -1,3 +1,25 @@
+// Hex strings in both directions. Decoding accepts either case; encoding is lowercase.
+// Lengths and indices in errors count characters of the input string.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum HexError {
+    OddLength(usize),
+    // the character and its index
+    InvalidCharacter(char, usize),
+    // an even length that doesn't decode to the fixed number of bytes expected
+    InvalidLength(usize),
+}
+
+pub trait ToHex {
+    fn to_hex(&self) -> String;
+}
+
+// Error wraps HexError so a bad string and a bad decoded value come back the same way
+pub trait FromHex: Sized {
+    type Error: From<HexError>;
+
+    fn from_hex(s: &str) -> Result<Self, Self::Error>;
+}
+
 #[allow(dead_code)]
 pub fn hex(bytes: &[u8]) -> String {
     bytes
@@ -5,3 +27,93 @@ pub fn hex(bytes: &[u8]) -> String {
         .map(|x| format!("{:02x}", x))
         .collect::<String>()
 }
+
+impl ToHex for [u8] {
+    fn to_hex(&self) -> String {
+        hex(self)
+    }
+}
+
+impl ToHex for Vec<u8> {
+    fn to_hex(&self) -> String {
+        hex(self)
+    }
+}
+
+impl<const N: usize> ToHex for [u8; N] {
+    fn to_hex(&self) -> String {
+        hex(self)
+    }
+}
+
+impl FromHex for Vec<u8> {
+    type Error = HexError;
+
+    fn from_hex(s: &str) -> Result<Self, HexError> {
+        let mut bytes = Vec::with_capacity(s.len() / 2);
+        let mut high = None;
+        for (i, c) in s.chars().enumerate() {
+            let digit = c.to_digit(16).ok_or(HexError::InvalidCharacter(c, i))? as u8;
+            match high.take() {
+                None => high = Some(digit),
+                Some(high) => bytes.push(high << 4 | digit),
+            }
+        }
+        if high.is_some() {
+            return Err(HexError::OddLength(s.chars().count()));
+        }
+        Ok(bytes)
+    }
+}
+
+impl<const N: usize> FromHex for [u8; N] {
+    type Error = HexError;
+
+    fn from_hex(s: &str) -> Result<Self, HexError> {
+        let bytes = Vec::from_hex(s)?;
+        bytes
+            .try_into()
+            .map_err(|_| HexError::InvalidLength(s.chars().count()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_test() {
+        let bytes = Vec::from_hex("00ff10AbcD").unwrap();
+        assert_eq!(bytes, vec![0x00, 0xff, 0x10, 0xab, 0xcd]);
+        assert_eq!(bytes.to_hex(), "00ff10abcd");
+        assert_eq!(bytes[1..3].to_hex(), "ff10");
+        assert_eq!(Vec::from_hex("").unwrap(), vec![]);
+
+        let array = <[u8; 4]>::from_hex("deadbeef").unwrap();
+        assert_eq!(array, [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(array.to_hex(), "deadbeef");
+    }
+
+    #[test]
+    fn invalid_test() {
+        assert_eq!(Vec::from_hex("abc"), Err(HexError::OddLength(3)));
+        assert_eq!(Vec::from_hex("0g"), Err(HexError::InvalidCharacter('g', 1)));
+        // the index counts characters, not bytes
+        assert_eq!(
+            Vec::from_hex("00é0"),
+            Err(HexError::InvalidCharacter('é', 2))
+        );
+        assert_eq!(
+            Vec::from_hex(" 00"),
+            Err(HexError::InvalidCharacter(' ', 0))
+        );
+        assert_eq!(
+            <[u8; 4]>::from_hex("deadbe"),
+            Err(HexError::InvalidLength(6))
+        );
+        assert_eq!(
+            <[u8; 2]>::from_hex("xx"),
+            Err(HexError::InvalidCharacter('x', 0))
+        );
+    }
+}
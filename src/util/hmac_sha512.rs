@@ -0,0 +1,7 @@
+use bitcoin_hashes::{hmac, sha512, Hash, HashEngine};
+
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut engine = hmac::HmacEngine::<sha512::Hash>::new(key);
+    engine.input(data);
+    hmac::Hmac::<sha512::Hash>::from_engine(engine).into_inner()
+}
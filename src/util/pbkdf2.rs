@@ -0,0 +1,21 @@
+use bitcoin_hashes::{hmac, sha512, Hash, HashEngine};
+
+// PBKDF2 (RFC 8018) with HMAC-SHA512, for a single 64 byte output block
+pub fn pbkdf2_hmac_sha512(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 64] {
+    let keyed = hmac::HmacEngine::<sha512::Hash>::new(password);
+
+    let mut engine = keyed.clone();
+    engine.input(salt);
+    engine.input(&1u32.to_be_bytes());
+    let mut u = hmac::Hmac::<sha512::Hash>::from_engine(engine).into_inner();
+    let mut result = u;
+    for _ in 1..iterations {
+        let mut engine = keyed.clone();
+        engine.input(&u);
+        u = hmac::Hmac::<sha512::Hash>::from_engine(engine).into_inner();
+        for (r, b) in result.iter_mut().zip(u.iter()) {
+            *r ^= b;
+        }
+    }
+    result
+}
@@ -0,0 +1,173 @@
+use super::hash_engine::HashEngine;
+use bitcoin_hashes::{sha256, Hash};
+
+// BIP340 tagged hash: sha256(sha256(tag) || sha256(tag) || msg)
+pub fn tagged_hash(tag: &str, msg: &[u8]) -> Vec<u8> {
+    let mut engine = tagged_engine(tag);
+    engine.input(msg);
+    engine.finalize()
+}
+
+// the double tag prefix is exactly one SHA256 block, so the message can be streamed after it
+pub fn tagged_engine(tag: &str) -> HashEngine {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = HashEngine::sha256();
+    engine.input(&tag_hash);
+    engine.input(&tag_hash);
+    engine
+}
+
+// Tags used by BIP340 (schnorr), BIP341 (taproot) and BIP352 (silent payments), with the
+// SHA256 state after their prefix worked out ahead of time.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Tag {
+    Bip340Challenge,
+    Bip340Aux,
+    Bip340Nonce,
+    TapLeaf,
+    TapBranch,
+    TapTweak,
+    TapSighash,
+    Bip352Inputs,
+    Bip352SharedSecret,
+    Bip352Label,
+}
+
+impl Tag {
+    pub const ALL: [Tag; 10] = [
+        Tag::Bip340Challenge,
+        Tag::Bip340Aux,
+        Tag::Bip340Nonce,
+        Tag::TapLeaf,
+        Tag::TapBranch,
+        Tag::TapTweak,
+        Tag::TapSighash,
+        Tag::Bip352Inputs,
+        Tag::Bip352SharedSecret,
+        Tag::Bip352Label,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tag::Bip340Challenge => "BIP0340/challenge",
+            Tag::Bip340Aux => "BIP0340/aux",
+            Tag::Bip340Nonce => "BIP0340/nonce",
+            Tag::TapLeaf => "TapLeaf",
+            Tag::TapBranch => "TapBranch",
+            Tag::TapTweak => "TapTweak",
+            Tag::TapSighash => "TapSighash",
+            Tag::Bip352Inputs => "BIP0352/Inputs",
+            Tag::Bip352SharedSecret => "BIP0352/SharedSecret",
+            Tag::Bip352Label => "BIP0352/Label",
+        }
+    }
+
+    fn midstate(self) -> [u8; 32] {
+        match self {
+            Tag::Bip340Challenge => [
+                0x9c, 0xec, 0xba, 0x11, 0x23, 0x92, 0x53, 0x81, 0x11, 0x67, 0x91, 0x12, 0xd1, 0x62,
+                0x7e, 0x0f, 0x97, 0xc8, 0x75, 0x50, 0x00, 0x3c, 0xc7, 0x65, 0x90, 0xf6, 0x11, 0x64,
+                0x33, 0xe9, 0xb6, 0x6a,
+            ],
+            Tag::Bip340Aux => [
+                0x24, 0xdd, 0x32, 0x19, 0x4e, 0xba, 0x7e, 0x70, 0xca, 0x0f, 0xab, 0xb9, 0x0f, 0xa3,
+                0x16, 0x6d, 0x3a, 0xfb, 0xe4, 0xb1, 0x4c, 0x44, 0xdf, 0x97, 0x4a, 0xac, 0x27, 0x39,
+                0x24, 0x9e, 0x85, 0x0a,
+            ],
+            Tag::Bip340Nonce => [
+                0x46, 0x61, 0x5b, 0x35, 0xf4, 0xbf, 0xbf, 0xf7, 0x9f, 0x8d, 0xc6, 0x71, 0x83, 0x62,
+                0x7a, 0xb3, 0x60, 0x21, 0x71, 0x80, 0x57, 0x35, 0x86, 0x61, 0x21, 0xa2, 0x9e, 0x54,
+                0x68, 0xb0, 0x7b, 0x4c,
+            ],
+            Tag::TapLeaf => [
+                0x9c, 0xe0, 0xe4, 0xe6, 0x7c, 0x11, 0x6c, 0x39, 0x38, 0xb3, 0xca, 0xf2, 0xc3, 0x0f,
+                0x50, 0x89, 0xd3, 0xf3, 0x93, 0x6c, 0x47, 0x63, 0x6e, 0x60, 0x7d, 0xb3, 0x3e, 0xea,
+                0xdd, 0xc6, 0xf0, 0xc9,
+            ],
+            Tag::TapBranch => [
+                0x23, 0xa8, 0x65, 0xa9, 0xb8, 0xa4, 0x0d, 0xa7, 0x97, 0x7c, 0x1e, 0x04, 0xc4, 0x9e,
+                0x24, 0x6f, 0xb5, 0xbe, 0x13, 0x76, 0x9d, 0x24, 0xc9, 0xb7, 0xb5, 0x83, 0xb5, 0xd4,
+                0xa8, 0xd2, 0x26, 0xd2,
+            ],
+            Tag::TapTweak => [
+                0xd1, 0x29, 0xa2, 0xf3, 0x70, 0x1c, 0x65, 0x5d, 0x65, 0x83, 0xb6, 0xc3, 0xb9, 0x41,
+                0x97, 0x27, 0x95, 0xf4, 0xe2, 0x32, 0x94, 0xfd, 0x54, 0xf4, 0xa2, 0xae, 0x8d, 0x85,
+                0x47, 0xca, 0x59, 0x0b,
+            ],
+            Tag::TapSighash => [
+                0xf5, 0x04, 0xa4, 0x25, 0xd7, 0xf8, 0x78, 0x3b, 0x13, 0x63, 0x86, 0x8a, 0xe3, 0xe5,
+                0x56, 0x58, 0x6e, 0xee, 0x94, 0x5d, 0xbc, 0x78, 0x88, 0xdd, 0x02, 0xa6, 0xe2, 0xc3,
+                0x18, 0x73, 0xfe, 0x9f,
+            ],
+            Tag::Bip352Inputs => [
+                0xd4, 0x14, 0x3f, 0xfc, 0x01, 0x2e, 0xa4, 0xb5, 0x36, 0xe2, 0x1c, 0x8f, 0xf7, 0xec,
+                0x7b, 0x54, 0x4d, 0xd4, 0xe2, 0xac, 0x9b, 0xca, 0xa0, 0xa4, 0xe2, 0x44, 0x89, 0x9b,
+                0xcd, 0x06, 0x90, 0x3e,
+            ],
+            Tag::Bip352SharedSecret => [
+                0x88, 0x83, 0x15, 0x37, 0x51, 0x27, 0x07, 0x9b, 0x69, 0xc2, 0x13, 0x7b, 0xab, 0x03,
+                0x03, 0xe6, 0x98, 0xfa, 0x21, 0xfa, 0x4a, 0x88, 0x85, 0x23, 0xbd, 0x99, 0xda, 0xab,
+                0xf2, 0x5e, 0x5e, 0x0a,
+            ],
+            Tag::Bip352Label => [
+                0x26, 0xb9, 0x5d, 0x63, 0x8b, 0xf1, 0xb7, 0x40, 0x10, 0xa5, 0x98, 0x6f, 0x06, 0xa3,
+                0x87, 0xa5, 0x2d, 0x1c, 0x1c, 0x30, 0xd0, 0x35, 0x95, 0x1a, 0x2d, 0x7f, 0x0f, 0x96,
+                0x29, 0xe3, 0xe0, 0xdb,
+            ],
+        }
+    }
+
+    // same as tagged_engine(self.name()) without hashing the tag
+    pub fn engine(self) -> HashEngine {
+        HashEngine::from_midstate(self.midstate(), 64).unwrap()
+    }
+
+    pub fn hash(self, msg: &[u8]) -> Vec<u8> {
+        let mut engine = self.engine();
+        engine.input(msg);
+        engine.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex::hex;
+    use std::io::Write;
+
+    #[test]
+    fn tagged_hash_test() {
+        // sha256(sha256("TapLeaf") || sha256("TapLeaf")) written out in one buffer
+        let tag_hash = sha256::Hash::hash(b"TapLeaf").to_vec();
+        let bytes = [tag_hash.clone(), tag_hash, b"msg".to_vec()].concat();
+        assert_eq!(
+            tagged_hash("TapLeaf", b"msg"),
+            sha256::Hash::hash(&bytes).to_vec()
+        );
+        assert_eq!(
+            hex(&Tag::Bip340Challenge.hash(&[0u8; 96])),
+            hex(&tagged_hash("BIP0340/challenge", &[0u8; 96]))
+        );
+    }
+
+    #[test]
+    fn midstate_test() {
+        for tag in Tag::ALL {
+            let mut precomputed = tag.engine();
+            let mut computed = tagged_engine(tag.name());
+            assert_eq!(precomputed.len(), computed.len());
+            precomputed.write_all(b"some message").unwrap();
+            computed.write_all(b"some message").unwrap();
+            assert_eq!(precomputed.finalize(), computed.finalize());
+        }
+    }
+
+    #[test]
+    fn tap_leaf_test() {
+        // leaf hash of a lone OP_TRUE script with leaf version 0xc0
+        assert_eq!(
+            hex(&Tag::TapLeaf.hash(&[0xc0, 0x01, 0x51])),
+            "a85b2107f791b26a84e7586c28cec7cb61202ed3d01944d832500f363782d675"
+        );
+    }
+}
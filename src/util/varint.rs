@@ -1,53 +1,121 @@
-use super::reader::read;
+// CompactSize, Bitcoin's variable length integer: values below 0xfd take one byte, larger
+// ones are marked with 0xfd, 0xfe or 0xff followed by a 2, 4 or 8 byte little-endian value.
+use super::encode::EncodeError;
 use std::io::Read;
 
-pub fn read_varint<R>(mut reader: R) -> Vec<u8>
+// largest size bitcoind accepts when reading a length
+pub const MAX_SIZE: u64 = 0x0200_0000;
+
+// Rejects values encoded in more bytes than needed, since those would give the same
+// transaction a second serialization, and values above MAX_SIZE.
+pub fn read_compact_size<R>(reader: &mut R) -> Result<u64, EncodeError>
 where
     R: Read,
 {
-    let buf_len1 = read(reader, 1);
-    let valid_len = match buf_len1[0] {
-        0xfd => 2,
-        0xfe => 4,
-        0xff => 8,
-        _ => 0,
+    let mut marker = [0u8; 1];
+    reader.read_exact(&mut marker)?;
+    let (len, min) = match marker[0] {
+        0xfd => (2, 0xfd),
+        0xfe => (4, 0x1_0000),
+        0xff => (8, 0x1_0000_0000),
+        n => return Ok(n as u64),
     };
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes[..len])?;
+    let n = u64::from_le_bytes(bytes);
+    if n < min {
+        return Err(EncodeError::NonCanonicalCompactSize);
+    }
+    if n > MAX_SIZE {
+        return Err(EncodeError::OversizedCompactSize(n));
+    }
+    Ok(n)
+}
 
-    if valid_len > 0 {
-        read(reader, valid_len)
-    } else {
-        buf_len1
+pub fn write_compact_size(n: u64) -> Vec<u8> {
+    match n {
+        0..=0xfc => vec![n as u8],
+        0xfd..=0xffff => [&[0xfd][..], &(n as u16).to_le_bytes()].concat(),
+        0x1_0000..=0xffff_ffff => [&[0xfe][..], &(n as u32).to_le_bytes()].concat(),
+        _ => [&[0xff][..], &n.to_le_bytes()].concat(),
     }
 }
 
-pub fn encode_varint(i: usize) -> Result<Vec<u8>, String> {
-    if i < 0xfd {
-        Ok(vec![i as u8])
-    } else if i < 0x10000 {
-        let mut bytes = i.to_le_bytes().to_vec();
-        bytes.extend(vec![0x0; 2 - bytes.len()]);
-        if !bytes.len() != 2 {
-            panic!("Error on encode_varint function: invarid byte length")
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn read(bytes: &[u8]) -> Result<u64, EncodeError> {
+        read_compact_size(&mut &bytes[..])
+    }
+
+    // serialize_tests.cpp: sizes, then i - 1 and i for every power of two up to MAX_SIZE
+    #[test]
+    fn roundtrip_test() {
+        for (n, len) in [
+            (0, 1),
+            (252, 1),
+            (253, 3),
+            (65535, 3),
+            (65536, 5),
+            (4294967295, 5),
+            (4294967296, 9),
+            (u64::MAX, 9),
+        ] {
+            assert_eq!(write_compact_size(n).len(), len);
         }
-        bytes.insert(0, 0xfd);
-        Ok(bytes)
-    } else if i < 0x100000000 {
-        let mut bytes = i.to_le_bytes().to_vec();
-        bytes.extend(vec![0x0; 4 - bytes.len()]);
-        if !bytes.len() != 4 {
-            panic!("Error on encode_varint function: invarid byte length")
+
+        let mut bytes = vec![];
+        let mut i = 1;
+        while i <= MAX_SIZE {
+            bytes.extend(write_compact_size(i - 1));
+            bytes.extend(write_compact_size(i));
+            i *= 2;
         }
-        bytes.insert(0, 0xfe);
-        Ok(bytes)
-    } else if i < 0x10000000000000000 {
-        let mut bytes = i.to_le_bytes().to_vec();
-        bytes.extend(vec![0x0; 8 - bytes.len()]);
-        if bytes.len() != 8 {
-            panic!("Error on encode_varint function: invarid byte length")
+        let mut reader = &bytes[..];
+        let mut i = 1;
+        while i <= MAX_SIZE {
+            assert_eq!(read_compact_size(&mut reader).unwrap(), i - 1);
+            assert_eq!(read_compact_size(&mut reader).unwrap(), i);
+            i *= 2;
         }
-        bytes.insert(0, 0xff);
-        Ok(bytes)
-    } else {
-        Err(String::from("VarintEncodingError"))
+        assert_eq!(true, reader.is_empty());
+    }
+
+    #[test]
+    fn noncanonical_test() {
+        let noncanonical = Err(EncodeError::NonCanonicalCompactSize);
+        assert_eq!(read(&[0xfd, 0x00, 0x00]), noncanonical);
+        assert_eq!(read(&[0xfd, 0xfc, 0x00]), noncanonical);
+        assert_eq!(read(&[0xfd, 0xfd, 0x00]), Ok(0xfd));
+        assert_eq!(read(&[0xfe, 0x00, 0x00, 0x00, 0x00]), noncanonical);
+        assert_eq!(read(&[0xfe, 0xff, 0xff, 0x00, 0x00]), noncanonical);
+        assert_eq!(read(&[0xff, 0, 0, 0, 0, 0, 0, 0, 0]), noncanonical);
+        assert_eq!(
+            read(&[0xff, 0xff, 0xff, 0xff, 0x01, 0x00, 0x00, 0x00, 0x00]),
+            noncanonical
+        );
+    }
+
+    #[test]
+    fn oversized_test() {
+        assert_eq!(read(&write_compact_size(MAX_SIZE)), Ok(MAX_SIZE));
+        assert_eq!(
+            read(&write_compact_size(MAX_SIZE + 1)),
+            Err(EncodeError::OversizedCompactSize(MAX_SIZE + 1))
+        );
+        assert_eq!(
+            read(&write_compact_size(u64::MAX)),
+            Err(EncodeError::OversizedCompactSize(u64::MAX))
+        );
+        assert_eq!(
+            read(&[0xfe, 0x00]),
+            Err(EncodeError::Io(io::ErrorKind::UnexpectedEof))
+        );
+        assert_eq!(
+            read(&[]),
+            Err(EncodeError::Io(io::ErrorKind::UnexpectedEof))
+        );
     }
 }